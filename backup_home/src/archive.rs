@@ -0,0 +1,320 @@
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use std::env;
+use std::fs;
+use std::fs::{File, Metadata};
+use std::io;
+use std::io::{Read, Write};
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::PathBuf;
+use std::process;
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+use std::thread::JoinHandle;
+
+use crate::manifest::Manifest;
+use crate::progress::{Progress, ProgressReader};
+use crate::volume;
+use crate::volume::{VolumeReader, VolumeWriter};
+
+static PASSPHRASE_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+// gpg reads the passphrase from a file only the user can open,
+// an argument would show up in the process list of every user
+struct PassphraseFile {
+    path: PathBuf,
+}
+
+impl PassphraseFile {
+    fn create(passphrase: &String) -> Result<PassphraseFile, io::Error> {
+        loop {
+            let path: PathBuf = env::temp_dir().join(format!(
+                "backup_home_{}_{}.pass",
+                process::id(),
+                PASSPHRASE_COUNT.fetch_add(1, Ordering::Relaxed)
+            ));
+            match File::options()
+                .write(true)
+                .create_new(true)
+                .mode(0o600)
+                .open(&path)
+            {
+                Ok(mut file) => {
+                    let passphrase_file: PassphraseFile = PassphraseFile { path };
+                    file.write_all(passphrase.as_bytes())?;
+                    return Ok(passphrase_file);
+                }
+                Err(err) if err.kind() == io::ErrorKind::AlreadyExists => continue,
+                Err(err) => return Err(err),
+            }
+        }
+    }
+}
+
+impl Drop for PassphraseFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+fn get_gpg_command(passphrase_file: &PassphraseFile) -> Command {
+    let mut command: Command = Command::new("gpg");
+    command
+        .args(["--batch", "--quiet", "--pinentry-mode", "loopback"])
+        .arg("--passphrase-file")
+        .arg(&passphrase_file.path);
+    command
+}
+
+enum Source {
+    Plain(VolumeReader),
+    Decrypted(
+        Child,
+        ChildStdout,
+        JoinHandle<io::Result<u64>>,
+        PassphraseFile,
+    ),
+}
+
+pub struct ArchiveReader {
+    source: Source,
+}
+
+impl ArchiveReader {
+    pub fn open(archive: &String, passphrase: &String) -> Result<ArchiveReader, io::Error> {
+        let mut volumes: VolumeReader = VolumeReader::open(archive)?;
+        if !archive.ends_with(".gpg") {
+            return Ok(ArchiveReader {
+                source: Source::Plain(volumes),
+            });
+        }
+
+        let passphrase_file: PassphraseFile = PassphraseFile::create(passphrase)?;
+        let mut gpg: Child = get_gpg_command(&passphrase_file)
+            .arg("-d")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()?;
+        let mut input: ChildStdin = gpg
+            .stdin
+            .take()
+            .ok_or(io::Error::other("failed to write gpg input"))?;
+        let output: ChildStdout = gpg
+            .stdout
+            .take()
+            .ok_or(io::Error::other("failed to read gpg output"))?;
+        let feeder: JoinHandle<io::Result<u64>> =
+            thread::spawn(move || io::copy(&mut volumes, &mut input));
+        Ok(ArchiveReader {
+            source: Source::Decrypted(gpg, output, feeder, passphrase_file),
+        })
+    }
+
+    pub fn tar(self) -> tar::Archive<GzDecoder<ArchiveReader>> {
+        tar::Archive::new(GzDecoder::new(self))
+    }
+
+    // takes the result of reading, so gpg is waited on even when reading failed,
+    // a wrong passphrase explains a broken gzip stream better than gzip does
+    pub fn finish<T>(self, result: Result<T, io::Error>) -> Result<T, io::Error> {
+        if let Source::Decrypted(mut gpg, mut output, feeder, passphrase_file) = self.source {
+            // reading to the end keeps gpg from failing on a closed pipe
+            let _ = io::copy(&mut output, &mut io::sink());
+            drop(output);
+            let success: bool = gpg.wait()?.success();
+            drop(passphrase_file);
+            if !success {
+                return Err(io::Error::other(
+                    "failed to decrypt archive (wrong passphrase?)",
+                ));
+            }
+            if feeder.join().is_err() {
+                return Err(io::Error::other("thread failed to join"));
+            }
+        }
+        result
+    }
+}
+
+impl Read for ArchiveReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        match &mut self.source {
+            Source::Plain(volumes) => volumes.read(buf),
+            Source::Decrypted(_, output, _, _) => output.read(buf),
+        }
+    }
+}
+
+enum Destination {
+    Plain(VolumeWriter),
+    Encrypted(
+        Child,
+        ChildStdin,
+        JoinHandle<io::Result<VolumeWriter>>,
+        PassphraseFile,
+    ),
+}
+
+pub struct ArchiveWriter {
+    destination: Destination,
+}
+
+impl ArchiveWriter {
+    pub fn create(
+        archive: &String,
+        passphrase: &String,
+        volume_size: Option<u64>,
+    ) -> Result<ArchiveWriter, io::Error> {
+        let mut volumes: VolumeWriter = VolumeWriter::create(archive, volume_size)?;
+        if !archive.ends_with(".gpg") {
+            return Ok(ArchiveWriter {
+                destination: Destination::Plain(volumes),
+            });
+        }
+
+        let passphrase_file: PassphraseFile = PassphraseFile::create(passphrase)?;
+        let mut gpg: Child = get_gpg_command(&passphrase_file)
+            .arg("--yes")
+            .arg("-c")
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()?;
+        let input: ChildStdin = gpg
+            .stdin
+            .take()
+            .ok_or(io::Error::other("failed to write gpg input"))?;
+        let mut output: ChildStdout = gpg
+            .stdout
+            .take()
+            .ok_or(io::Error::other("failed to read gpg output"))?;
+        let collector: JoinHandle<io::Result<VolumeWriter>> = thread::spawn(move || {
+            io::copy(&mut output, &mut volumes)?;
+            Ok(volumes)
+        });
+        Ok(ArchiveWriter {
+            destination: Destination::Encrypted(gpg, input, collector, passphrase_file),
+        })
+    }
+
+    pub fn finish(self) -> Result<usize, io::Error> {
+        match self.destination {
+            Destination::Plain(volumes) => volumes.finish(),
+            Destination::Encrypted(mut gpg, input, collector, passphrase_file) => {
+                drop(input);
+                let success: bool = gpg.wait()?.success();
+                drop(passphrase_file);
+                if !success {
+                    return Err(io::Error::other("failed to encrypt archive"));
+                }
+                let volumes: io::Result<VolumeWriter> = collector
+                    .join()
+                    .map_err(|_| io::Error::other("thread failed to join"))?;
+                volumes?.finish()
+            }
+        }
+    }
+}
+
+impl Write for ArchiveWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match &mut self.destination {
+            Destination::Plain(volumes) => volumes.write(buf),
+            Destination::Encrypted(_, input, _, _) => input.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match &mut self.destination {
+            Destination::Plain(volumes) => volumes.flush(),
+            Destination::Encrypted(_, input, _, _) => input.flush(),
+        }
+    }
+}
+
+pub struct ArchiveStats {
+    pub files: usize,
+    pub bytes: u64,
+    pub skipped: Vec<String>,
+    pub volumes: usize,
+    pub output_bytes: u64,
+}
+
+pub fn create(
+    home_dir: &String,
+    archive: &String,
+    passphrase: &String,
+    volume_size: Option<u64>,
+    manifest: &Manifest,
+    paths: &[String],
+    progress: &Mutex<Progress>,
+) -> Result<ArchiveStats, io::Error> {
+    let mut builder: tar::Builder<GzEncoder<ArchiveWriter>> = tar::Builder::new(GzEncoder::new(
+        ArchiveWriter::create(archive, passphrase, volume_size)?,
+        Compression::default(),
+    ));
+
+    let mut stats: ArchiveStats = ArchiveStats {
+        files: 0,
+        bytes: 0,
+        skipped: vec![],
+        volumes: 0,
+        output_bytes: 0,
+    };
+    for path in paths {
+        let file: io::Result<(File, Metadata)> =
+            File::open(format!("{}/{}", home_dir, path)).and_then(|f| f.metadata().map(|m| (f, m)));
+        if file.is_err() {
+            if let Ok(mut progress) = progress.lock() {
+                progress.add_bytes(manifest.files[path].size);
+                progress.add_file();
+            }
+            stats.skipped.push(path.to_string());
+            continue;
+        }
+        let (file, meta_data): (File, Metadata) = file.unwrap();
+
+        let mut header: tar::Header = tar::Header::new_gnu();
+        header.set_metadata(&meta_data);
+        builder.append_data(
+            &mut header,
+            path,
+            ProgressReader::new(file.take(meta_data.len()), progress),
+        )?;
+        if let Ok(mut progress) = progress.lock() {
+            progress.add_file();
+        }
+        stats.files += 1;
+        stats.bytes += meta_data.len();
+    }
+    stats.volumes = builder.into_inner()?.finish()?.finish()?;
+    for volume in volume::get_volumes(archive)? {
+        stats.output_bytes += fs::metadata(volume)?.len();
+    }
+    Ok(stats)
+}
+
+fn count_entries(
+    reader: &mut tar::Archive<GzDecoder<ArchiveReader>>,
+) -> Result<(usize, u64), io::Error> {
+    let mut files: usize = 0;
+    let mut bytes: u64 = 0;
+    for entry in reader.entries()? {
+        let mut entry = entry?;
+        if entry.header().entry_type().is_file() {
+            files += 1;
+            bytes += io::copy(&mut entry, &mut io::sink())?;
+        }
+    }
+    Ok((files, bytes))
+}
+
+pub fn list(archive: &String, passphrase: &String) -> Result<(usize, u64), io::Error> {
+    let mut reader: tar::Archive<GzDecoder<ArchiveReader>> =
+        ArchiveReader::open(archive, passphrase)?.tar();
+    let counted: Result<(usize, u64), io::Error> = count_entries(&mut reader);
+    reader.into_inner().into_inner().finish(counted)
+}
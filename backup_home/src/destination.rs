@@ -0,0 +1,336 @@
+use rust_cli::ansi::Color;
+use rust_cli::ansi::Font;
+use rust_cli::prompts::select::Select;
+use rust_cli::prompts::text::Text;
+
+use std::env;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+use crate::helper;
+use crate::profile::Profile;
+
+pub enum Destination {
+    Local(String),
+    Remote(String),
+}
+
+impl Destination {
+    fn parse(value: &String, home_dir: &String) -> Destination {
+        if value == "home" {
+            return Destination::Local(home_dir.to_string());
+        }
+        if !value.starts_with('/') && value.contains(':') {
+            return Destination::Remote(value.to_string());
+        }
+        Destination::Local(value.trim_end_matches('/').to_string())
+    }
+
+    pub fn select(home_dir: &String, profile: &Profile) -> Result<Destination, io::Error> {
+        if let Some(value) = &profile.destination {
+            println!(
+                "Using destination {}{}{} from profile",
+                Color::Cyan.as_str(),
+                value,
+                Font::Reset.as_str()
+            );
+            return Ok(Destination::parse(value, home_dir));
+        }
+
+        let mut options_display: Vec<String> = vec![format!("Home ({}/backups/home)", home_dir)];
+        let mut options_value: Vec<Option<String>> = vec![Some(home_dir.to_string())];
+        for drive in get_removable_drives() {
+            options_display.push(format!("Removable drive ({})", drive));
+            options_value.push(Some(drive));
+        }
+        options_display.push("Other mounted path".to_string());
+        options_value.push(None);
+        options_display.push("Remote (rsync/ssh)".to_string());
+        options_value.push(None);
+
+        let selection = Select::new()
+            .title("Select backup destination")
+            .options(&options_display)
+            .run_select()?;
+        if selection.is_none() {
+            return Err(io::Error::other("no destination selected"));
+        }
+        let selection: usize = selection.unwrap().0;
+
+        if let Some(path) = &options_value[selection] {
+            return Ok(Destination::Local(path.to_string()));
+        }
+
+        if options_display[selection] == "Other mounted path" {
+            let path: String = Text::new("Destination path:").required(true).run()?;
+            let path: String = path.trim().trim_end_matches('/').to_string();
+            if !Path::new(&path).is_dir() {
+                return Err(io::Error::other(format!("{} is not a directory", path)));
+            }
+            return Ok(Destination::Local(path));
+        }
+
+        let target: String = Text::new("Remote target (user@host:path):")
+            .required(true)
+            .run()?;
+        let target: String = target.trim().trim_end_matches('/').to_string();
+        if !target.contains(':') {
+            return Err(io::Error::other("remote target must be user@host:path"));
+        }
+        Ok(Destination::Remote(target))
+    }
+
+    pub fn get_backup_dir(&self, home_dir: &String) -> String {
+        match self {
+            Destination::Local(path) => format!("{}/backups/home", path),
+            // every target stages in its own folder, apart from the home destination
+            Destination::Remote(target) => format!(
+                "{}/.cache/backup_home/{}",
+                home_dir,
+                get_staging_name(target)
+            ),
+        }
+    }
+
+    pub fn check_free_space(&self, backup_dir: &String, estimate: u64) -> Result<(), io::Error> {
+        let mut locations: Vec<(String, u64)> = vec![(
+            backup_dir.to_string(),
+            get_free_space(&mut Command::new("df"), backup_dir)?,
+        )];
+        if let Destination::Remote(target) = self {
+            let (host, path): (&str, &str) = target.split_once(':').unwrap_or((target, "."));
+            let path: &str = if path.is_empty() { "." } else { path };
+            // ssh joins its arguments into one line for the remote shell
+            let path: String = quote(path);
+            // df fails on a path that does not exist yet, which is every first run
+            let status = Command::new("ssh")
+                .arg(host)
+                .arg("--")
+                .arg("mkdir")
+                .arg("-p")
+                .arg("--")
+                .arg(&path)
+                .status()?;
+            if !status.success() {
+                return Err(io::Error::other(format!("failed to create {}", target)));
+            }
+            let mut ssh: Command = Command::new("ssh");
+            ssh.arg(host).arg("--").arg("df");
+            locations.push((target.to_string(), get_free_space(&mut ssh, &path)?));
+        }
+
+        for (location, free) in locations {
+            println!(
+                "{} has {}{}{} free, backup needs up to {}{}{}",
+                location,
+                Color::Cyan.as_str(),
+                helper::get_size_string(free),
+                Font::Reset.as_str(),
+                Color::Cyan.as_str(),
+                helper::get_size_string(estimate),
+                Font::Reset.as_str()
+            );
+            if free < estimate {
+                return Err(io::Error::other(format!(
+                    "not enough free space on {}",
+                    location
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    // the staged copy may have been deleted to free space, so it is filled again first
+    pub fn fetch(&self, backup_dir: &String) -> Result<(), io::Error> {
+        if let Destination::Remote(target) = self {
+            println!("Fetching backups from {}...", target);
+            if !rsync(target, backup_dir, false)? {
+                return Err(io::Error::other(format!(
+                    "failed to fetch backups from {}",
+                    target
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    // delete removes what is no longer staged, like snapshots dropped by prune
+    pub fn sync(&self, backup_dir: &String, delete: bool) -> Result<(), io::Error> {
+        if let Destination::Remote(target) = self {
+            println!("Syncing backups to {}...", target);
+            if !rsync(backup_dir, target, delete)? {
+                return Err(io::Error::other(format!(
+                    "failed to sync backups to {}",
+                    target
+                )));
+            }
+            // incremental archives and the repository need the previous run to compare against
+            println!(
+                "Kept the staged copy in {} ({}) for the next incremental backup, delete it to free the space",
+                backup_dir,
+                helper::get_size_string(get_dir_size(Path::new(backup_dir)))
+            );
+        }
+        Ok(())
+    }
+}
+
+fn quote(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "'\\''"))
+}
+
+fn rsync(source: &String, destination: &String, delete: bool) -> Result<bool, io::Error> {
+    let mut rsync: Command = Command::new("rsync");
+    // paths are passed to the remote side as they are, not through its shell
+    rsync
+        .arg("-a")
+        .arg("--protect-args")
+        .arg("--info=progress2");
+    if delete {
+        rsync.arg("--delete");
+    }
+    let status = rsync
+        .arg(format!("{}/", source))
+        .arg(format!("{}/", destination))
+        .status()?;
+    Ok(status.success())
+}
+
+fn get_staging_name(target: &str) -> String {
+    target
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '.' || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+fn get_dir_size(path: &Path) -> u64 {
+    let dir = fs::read_dir(path);
+    if dir.is_err() {
+        return 0;
+    }
+    let mut size: u64 = 0;
+    for entry in dir.unwrap().flatten() {
+        let meta_data: io::Result<fs::Metadata> = entry.metadata();
+        if meta_data.is_err() {
+            continue;
+        }
+        let meta_data: fs::Metadata = meta_data.unwrap();
+        if meta_data.is_dir() {
+            size += get_dir_size(&entry.path());
+        } else {
+            size += meta_data.len();
+        }
+    }
+    size
+}
+
+fn get_removable_drives() -> Vec<String> {
+    let mut drives: Vec<String> = vec![];
+    let user: Result<String, env::VarError> = env::var("USER");
+    if user.is_err() {
+        return drives;
+    }
+
+    let dir = fs::read_dir(format!("/run/media/{}", user.unwrap()));
+    if dir.is_err() {
+        return drives;
+    }
+    for entry in dir.unwrap() {
+        if entry.is_err() {
+            continue;
+        }
+        let entry_path: PathBuf = entry.unwrap().path();
+        if !entry_path.is_dir() {
+            continue;
+        }
+        if let Some(path) = entry_path.to_str() {
+            drives.push(path.to_string());
+        }
+    }
+    drives.sort();
+    drives
+}
+
+fn get_free_space(df: &mut Command, path: &str) -> Result<u64, io::Error> {
+    let output: Output = df.arg("-P").arg("-B1").arg(path).output()?;
+    if !output.status.success() {
+        return Err(io::Error::other(format!(
+            "failed to check free space of {}",
+            path
+        )));
+    }
+
+    let output: String = String::from_utf8_lossy(&output.stdout).to_string();
+    output
+        .lines()
+        .nth(1)
+        .and_then(|l| l.split_whitespace().nth(3))
+        .and_then(|a| a.parse::<u64>().ok())
+        .ok_or(io::Error::other(format!(
+            "failed to check free space of {}",
+            path
+        )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn values_are_parsed() {
+        let home_dir: String = String::from("/home/user");
+        assert!(matches!(
+            Destination::parse(&String::from("home"), &home_dir),
+            Destination::Local(path) if path == home_dir
+        ));
+        assert!(matches!(
+            Destination::parse(&String::from("/run/media/user/drive/"), &home_dir),
+            Destination::Local(path) if path == "/run/media/user/drive"
+        ));
+        assert!(matches!(
+            Destination::parse(&String::from("user@host:backups"), &home_dir),
+            Destination::Remote(target) if target == "user@host:backups"
+        ));
+    }
+
+    #[test]
+    fn remote_paths_are_quoted() {
+        assert_eq!(quote("backups"), "'backups'");
+        assert_eq!(quote("My Backups"), "'My Backups'");
+        assert_eq!(quote("$(rm -rf ~);"), "'$(rm -rf ~);'");
+        assert_eq!(quote("it's"), "'it'\\''s'");
+    }
+
+    #[test]
+    fn backup_dir_of_local_destination() {
+        let destination: Destination = Destination::Local(String::from("/mnt/drive"));
+        assert_eq!(
+            destination.get_backup_dir(&String::from("/home/user")),
+            "/mnt/drive/backups/home"
+        );
+    }
+
+    #[test]
+    fn remote_destination_is_staged_per_target() {
+        let home_dir: String = String::from("/home/user");
+        let destination: Destination =
+            Destination::Remote(String::from("user@host:/srv/My Backups"));
+        assert_eq!(
+            destination.get_backup_dir(&home_dir),
+            "/home/user/.cache/backup_home/user_host__srv_My_Backups"
+        );
+        let other: Destination = Destination::Remote(String::from("user@other:/srv"));
+        assert_ne!(
+            other.get_backup_dir(&home_dir),
+            destination.get_backup_dir(&home_dir)
+        );
+    }
+}
@@ -0,0 +1,66 @@
+pub fn get_size_string(bytes: u64) -> String {
+    if bytes > 1024 * 1024 * 1024 {
+        format!("{:.1} GB", bytes as f64 / (1024 * 1024 * 1024) as f64)
+    } else if bytes > 1024 * 1024 {
+        format!("{:.1} MB", bytes as f64 / (1024 * 1024) as f64)
+    } else if bytes > 1024 {
+        format!("{:.1} KB", bytes as f64 / 1024_f64)
+    } else {
+        format!("{} B", bytes)
+    }
+}
+
+pub fn parse_size(size: &str) -> Option<u64> {
+    let size: String = size.trim().to_uppercase();
+    let size: &str = size.trim_end_matches("IB").trim_end_matches('B');
+    let (number, unit): (&str, u64) = match size.chars().last() {
+        Some('K') => (&size[..size.len() - 1], 1024),
+        Some('M') => (&size[..size.len() - 1], 1024 * 1024),
+        Some('G') => (&size[..size.len() - 1], 1024 * 1024 * 1024),
+        Some('T') => (&size[..size.len() - 1], 1024 * 1024 * 1024 * 1024),
+        _ => (size, 1),
+    };
+    let number: f64 = number.trim().parse::<f64>().ok()?;
+    if number <= 0.0 {
+        return None;
+    }
+    Some((number * unit as f64) as u64)
+}
+
+// manifests and indexes keep one file per line, names may hold any of these
+pub fn escape_path(path: &str) -> String {
+    let mut escaped: String = String::with_capacity(path.len());
+    for c in path.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '\t' => escaped.push_str("\\t"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+pub fn unescape_path(path: &str) -> String {
+    let mut unescaped: String = String::with_capacity(path.len());
+    let mut chars = path.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('\\') => unescaped.push('\\'),
+            Some('t') => unescaped.push('\t'),
+            Some('n') => unescaped.push('\n'),
+            Some('r') => unescaped.push('\r'),
+            Some(c) => {
+                unescaped.push('\\');
+                unescaped.push(c);
+            }
+            None => unescaped.push('\\'),
+        }
+    }
+    unescaped
+}
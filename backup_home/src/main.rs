@@ -1,26 +1,289 @@
 extern crate rust_cli;
 
-use rust_cli::commands::Operation;
+mod archive;
+mod destination;
+mod helper;
+mod manifest;
+mod profile;
+mod progress;
+mod report;
+mod repository;
+mod restore;
+mod snapshot;
+mod verify;
+mod volume;
+
+use rust_cli::ansi::Color;
+use rust_cli::ansi::Font;
 use rust_cli::prompts::confirm::Confirm;
 use rust_cli::prompts::select::Select;
 use rust_cli::prompts::text::Text;
 
+use chrono::prelude::Local;
 use std::env;
 use std::env::VarError;
 use std::fs;
 use std::io::Error;
+use std::num::ParseIntError;
+use std::sync::mpsc;
+use std::sync::mpsc::{Receiver, Sender};
+use std::sync::Mutex;
+use std::thread;
+use std::time::Instant;
 
-fn main() -> Result<(), Error> {
-    let home_dir: Result<String, VarError> = env::var("HOME");
-    if home_dir.is_err() {
-        return Err(Error::other("HOME directory could not be determined"));
+use crate::archive::ArchiveStats;
+use crate::destination::Destination;
+use crate::manifest::Manifest;
+use crate::profile::Profile;
+use crate::progress::Progress;
+use crate::report::{FolderReport, Report};
+use crate::repository::Repository;
+use crate::snapshot::{Snapshot, SnapshotKind};
+
+fn print_help() {
+    println!();
+    println!("Backup Home");
+    println!("Command line program that backs up folders in the home directory");
+    println!();
+    println!("Usage: backup_home [COMMAND]");
+    println!();
+    println!("Commands:");
+    println!("  restore     Restore files from a backup snapshot into a directory");
+    println!("  verify      Check existing archives against their checksum files");
+    println!("  snapshots   List snapshots stored in the repository");
+    println!("  prune       Remove old repository snapshots and unused chunks");
+    println!("  -h, --help  Print help information");
+    println!();
+    println!("Profile:");
+    println!("  ~/.config/backup_home/profile");
+    println!("  destination=<home|PATH|USER@HOST:PATH>");
+    println!("  volume_size=<SIZE>  (e.g. 3.9G)");
+    println!("  threads=<COUNT>     (folders archived in parallel)");
+    println!();
+    println!("Remote destinations are staged in ~/.cache/backup_home/<TARGET> first.");
+    println!("The staged copy is kept so the next backup can be incremental.");
+    println!("Other commands fetch the remote backups into the staged copy first.");
+    println!();
+}
+
+// the scan already says why it skipped something, the archive only fails on reads
+fn get_skipped(scanned: Vec<String>, unreadable: &[String]) -> Vec<String> {
+    let unreadable: Vec<String> = unreadable
+        .iter()
+        .map(|path| format!("{} (could not be read)", path))
+        .collect();
+    [scanned, unreadable].concat()
+}
+
+fn get_skipped_lines(report: &FolderReport) -> Vec<String> {
+    report
+        .skipped
+        .iter()
+        .map(|path| {
+            format!(
+                "    {}Skipped{} {}",
+                Color::Yellow.as_str(),
+                Font::Reset.as_str(),
+                path
+            )
+        })
+        .collect()
+}
+
+fn prune(backup_dir: &String) -> Result<(), Error> {
+    let repository: Repository = Repository::new(backup_dir);
+    if !repository.exists() {
+        return Err(Error::other("no repository found to prune"));
     }
-    let home_dir: String = home_dir.unwrap();
 
-    let backup_dir: String = format!("{}/backups", home_dir);
-    fs::create_dir_all(&backup_dir)?;
+    let keep: String = Text::new("Number of snapshots to keep per folder:")
+        .required(true)
+        .run()?;
+    let keep: Result<usize, ParseIntError> = keep.trim().parse::<usize>();
+    if keep.as_ref().is_err() || keep.as_ref().is_ok_and(|k| *k == 0) {
+        return Err(Error::other(
+            "number of snapshots to keep must be at least 1",
+        ));
+    }
+    repository.prune(keep.unwrap())
+}
+
+struct FolderBackup {
+    folder: String,
+    manifest: Manifest,
+    kind: SnapshotKind,
+    paths: Vec<String>,
+    encrypt: bool,
+    volume_size: Option<u64>,
+}
 
-    let backup_dir: String = format!("{}/home", backup_dir);
+impl FolderBackup {
+    fn get_estimate(&self) -> u64 {
+        self.paths.iter().map(|p| self.manifest.files[p].size).sum()
+    }
+}
+
+fn backup_folder(
+    home_dir: &String,
+    backup_dir: &String,
+    now: &String,
+    passphrase: &String,
+    mut folder_backup: FolderBackup,
+    progress: &Mutex<Progress>,
+    log: &mut Vec<String>,
+) -> Result<FolderReport, Error> {
+    let start: Instant = Instant::now();
+    let folder: &String = &folder_backup.folder;
+    let folder_dir: String = format!("{backup_dir}/{folder}");
+    fs::create_dir_all(&folder_dir)?;
+
+    let mut archive: String = format!("{folder_dir}/{now}_{}.tar.gz", folder_backup.kind.as_str());
+    if folder_backup.encrypt {
+        archive.push_str(".gpg");
+        log.push(format!(
+            "Compressed and encrypted {} ({} of {} files)",
+            folder,
+            folder_backup.paths.len(),
+            folder_backup.manifest.files.len()
+        ));
+    } else {
+        log.push(format!(
+            "Compressed {} ({} of {} files)",
+            folder,
+            folder_backup.paths.len(),
+            folder_backup.manifest.files.len()
+        ));
+    }
+
+    let stats: ArchiveStats = archive::create(
+        home_dir,
+        &archive,
+        passphrase,
+        folder_backup.volume_size,
+        &folder_backup.manifest,
+        &folder_backup.paths,
+        progress,
+    )?;
+    if stats.volumes > 1 {
+        log.push(format!("    Split into {} volumes", stats.volumes));
+    }
+    for path in &stats.skipped {
+        folder_backup.manifest.files.remove(path);
+    }
+
+    verify::check_archive(&archive, passphrase, &stats)?;
+    verify::write_checksum(&archive)?;
+    log.push(format!(
+        "    {}Verified{} {}",
+        Color::Green.as_str(),
+        Font::Reset.as_str(),
+        &archive
+    ));
+
+    folder_backup
+        .manifest
+        .write(&format!("{folder_dir}/{now}.manifest"))?;
+
+    let mut report: FolderReport = FolderReport::new(folder, "archive");
+    report.files = stats.files;
+    report.set_bytes(stats.bytes, stats.output_bytes);
+    report.encrypted = folder_backup.encrypt;
+    report.skipped = get_skipped(folder_backup.manifest.skipped, &stats.skipped);
+    report.duration_seconds = start.elapsed().as_secs_f64();
+    log.extend(get_skipped_lines(&report));
+    Ok(report)
+}
+
+fn run_folder_backups(
+    home_dir: &String,
+    backup_dir: &String,
+    now: &String,
+    passphrase: &String,
+    folder_backups: Vec<FolderBackup>,
+    threads: usize,
+) -> Vec<FolderReport> {
+    let total_files: usize = folder_backups.iter().map(|f| f.paths.len()).sum();
+    let total_bytes: u64 = folder_backups.iter().map(|f| f.get_estimate()).sum();
+    let progress: Mutex<Progress> = Mutex::new(Progress::new(total_files, total_bytes));
+
+    let threads: usize = threads.clamp(1, folder_backups.len().max(1));
+    println!(
+        "Backing up {} folders with {} worker threads...",
+        folder_backups.len(),
+        threads
+    );
+
+    let queue: Mutex<Vec<FolderBackup>> = Mutex::new(folder_backups.into_iter().rev().collect());
+    let (sender, receiver): (Sender<(FolderReport, Vec<String>)>, Receiver<_>) = mpsc::channel();
+    let mut reports: Vec<FolderReport> = vec![];
+    thread::scope(|scope| {
+        for _ in 0..threads {
+            let sender: Sender<(FolderReport, Vec<String>)> = sender.clone();
+            let queue: &Mutex<Vec<FolderBackup>> = &queue;
+            let progress: &Mutex<Progress> = &progress;
+            scope.spawn(move || loop {
+                let folder_backup: Option<FolderBackup> =
+                    queue.lock().ok().and_then(|mut q| q.pop());
+                if folder_backup.is_none() {
+                    break;
+                }
+                let folder_backup: FolderBackup = folder_backup.unwrap();
+
+                let start: Instant = Instant::now();
+                let folder: String = folder_backup.folder.to_string();
+                let encrypt: bool = folder_backup.encrypt;
+                let mut log: Vec<String> = vec![];
+                let result: Result<FolderReport, Error> = backup_folder(
+                    home_dir,
+                    backup_dir,
+                    now,
+                    passphrase,
+                    folder_backup,
+                    progress,
+                    &mut log,
+                );
+                let folder_report: FolderReport = match result {
+                    Ok(folder_report) => folder_report,
+                    Err(err) => {
+                        log.push(format!(
+                            "{}Failed{} {}: {}",
+                            Color::Red.as_str(),
+                            Font::Reset.as_str(),
+                            &folder,
+                            err
+                        ));
+                        let mut folder_report: FolderReport = FolderReport::new(&folder, "archive");
+                        folder_report.encrypted = encrypt;
+                        folder_report.error = Some(err.to_string());
+                        folder_report.duration_seconds = start.elapsed().as_secs_f64();
+                        folder_report
+                    }
+                };
+                if sender.send((folder_report, log)).is_err() {
+                    break;
+                }
+            });
+        }
+        drop(sender);
+
+        for (folder_report, log) in receiver {
+            if let Ok(mut progress) = progress.lock() {
+                progress.print_lines(&log);
+            }
+            reports.push(folder_report);
+        }
+    });
+    if let Ok(mut progress) = progress.lock() {
+        progress.finish();
+    }
+
+    reports.sort_by(|a, b| a.folder.cmp(&b.folder));
+    reports
+}
+
+fn backup(home_dir: &String, profile: &Profile) -> Result<(), Error> {
+    let destination: Destination = Destination::select(home_dir, profile)?;
+    let backup_dir: String = destination.get_backup_dir(home_dir);
     fs::create_dir_all(&backup_dir)?;
 
     let all_folders: Vec<&str> = vec!["Documents", "Music", "Pictures", "Videos"];
@@ -36,6 +299,58 @@ fn main() -> Result<(), Error> {
         return Err(Error::other("no folders selected to backup"));
     }
 
+    let now: String = Local::now().format("%Y%m%d_%H%M%S").to_string();
+
+    let mut report: Report = Report {
+        timestamp: now.to_string(),
+        backup_dir: backup_dir.to_string(),
+        folders: vec![],
+        sync_error: None,
+    };
+
+    let backup_formats: Vec<&str> = vec!["Archives", "Repository (deduplicated)"];
+    let use_repository: bool = Select::new()
+        .title("Select backup format")
+        .options(&backup_formats)
+        .run_select()?
+        .is_some_and(|t| t.0 == 1);
+    if use_repository {
+        let repository: Repository = Repository::new(&backup_dir);
+
+        let mut manifests: Vec<(String, Manifest)> = vec![];
+        let mut estimate: u64 = 0;
+        for folder in backup_folders {
+            let current: Manifest = Manifest::scan(home_dir, &folder);
+            estimate += repository.get_changed_size(&folder, &current)?;
+            manifests.push((folder, current));
+        }
+        destination.check_free_space(&backup_dir, estimate)?;
+
+        for (folder, current) in manifests {
+            let start: Instant = Instant::now();
+            println!("Storing {} ({} files)...", &folder, current.files.len());
+            let stats: ArchiveStats = repository.backup(home_dir, &folder, &now, &current)?;
+
+            let mut folder_report: FolderReport = FolderReport::new(&folder, "repository");
+            folder_report.files = stats.files;
+            folder_report.set_bytes(stats.bytes, stats.output_bytes);
+            folder_report.skipped = get_skipped(current.skipped, &stats.skipped);
+            folder_report.duration_seconds = start.elapsed().as_secs_f64();
+            for line in get_skipped_lines(&folder_report) {
+                println!("{}", line);
+            }
+            report.folders.push(folder_report);
+        }
+        return sync_backups(&destination, &backup_dir, &mut report);
+    }
+
+    let backup_types: Vec<&str> = vec!["Incremental", "Full"];
+    let incremental: bool = Select::new()
+        .title("Select backup type")
+        .options(&backup_types)
+        .run_select()?
+        .is_some_and(|t| t.0 == 0);
+
     let mut encrypt_folders: Vec<String> = vec![];
     let mut passphrase: String = String::new();
     if Confirm::new("Do you want to encrypt backups?").run()? {
@@ -58,30 +373,143 @@ fn main() -> Result<(), Error> {
             .run()?;
     }
 
+    let mut volume_size: Option<u64> = profile.volume_size;
+    if volume_size.is_none() && Confirm::new("Do you want to split archives into volumes?").run()? {
+        let size: String = Text::new("Volume size (e.g. 3.9G):").required(true).run()?;
+        volume_size = volume::parse_volume_size(&size);
+        if volume_size.is_none() {
+            return Err(Error::other(format!(
+                "{} is not a valid volume size (at least 1M)",
+                size
+            )));
+        }
+    }
+
+    let mut folder_backups: Vec<FolderBackup> = vec![];
     for folder in backup_folders {
-        let tar_file: String = format!("{backup_dir}/{folder}.tar.gz");
-        let crypt_file: String = format!("{backup_dir}/{folder}.tar.gz.gpg");
+        let manifest: Manifest = Manifest::scan(home_dir, &folder);
 
-        Operation::new(&format!("rm -f {}", &tar_file)).run()?;
-        Operation::new(&format!("rm -f {}", &crypt_file)).run()?;
+        let mut kind: SnapshotKind = SnapshotKind::Full;
+        let mut paths: Vec<String> = manifest.paths();
+        if incremental {
+            let snapshots: Vec<Snapshot> =
+                snapshot::get_snapshots(&format!("{backup_dir}/{folder}"))?;
+            if let Some(last) = snapshots.last() {
+                let previous: Manifest = Manifest::read(&last.manifest)?;
+                kind = SnapshotKind::Incremental;
+                paths = manifest.changed_paths(&previous);
+            }
+        }
 
-        println!("Compressing {}...", &folder);
-        Operation::new(format!("tar --exclude-vcs -cvzf {} {}", &tar_file, &folder))
-            .current_dir(&home_dir)
-            .run()?;
+        folder_backups.push(FolderBackup {
+            encrypt: encrypt_folders.contains(&folder),
+            volume_size,
+            folder,
+            manifest,
+            kind,
+            paths,
+        });
+    }
 
-        if encrypt_folders.contains(&folder) {
-            println!("Encrypting {}...", &folder);
-            Operation::new(format!(
-                "gpg --batch -c --passphrase {} {}",
-                &passphrase, &tar_file
-            ))
-            .current_dir(&home_dir)
-            .run()?;
+    let estimate: u64 = folder_backups.iter().map(|f| f.get_estimate()).sum();
+    destination.check_free_space(&backup_dir, estimate)?;
 
-            Operation::new(&format!("rm -f {}", &tar_file)).run()?;
-        }
+    let threads: usize = profile.threads.unwrap_or(
+        thread::available_parallelism()
+            .map(|n| n.get())
+            .unwrap_or(1),
+    );
+    report.folders = run_folder_backups(
+        home_dir,
+        &backup_dir,
+        &now,
+        &passphrase,
+        folder_backups,
+        threads,
+    );
+
+    sync_backups(&destination, &backup_dir, &mut report)?;
+
+    let failed: usize = report.folders.iter().filter(|f| f.error.is_some()).count();
+    if failed > 0 {
+        return Err(Error::other(format!(
+            "{} folders failed to back up",
+            failed
+        )));
     }
+    Ok(())
+}
 
+fn finish_report(report: &Report) -> Result<(), Error> {
+    report.print();
+    let report_file: String = report.save()?;
+    println!(
+        "Report saved to {}{}.json{} and {}{}.md{}",
+        Color::Cyan.as_str(),
+        &report_file,
+        Font::Reset.as_str(),
+        Color::Cyan.as_str(),
+        &report_file,
+        Font::Reset.as_str()
+    );
     Ok(())
 }
+
+// the report is saved first so it is synced too, and a run that archived everything still has one
+fn sync_backups(
+    destination: &Destination,
+    backup_dir: &String,
+    report: &mut Report,
+) -> Result<(), Error> {
+    finish_report(report)?;
+    if let Err(err) = destination.sync(backup_dir, false) {
+        report.sync_error = Some(err.to_string());
+        report.save()?;
+        return Err(err);
+    }
+    Ok(())
+}
+
+// remote backups are read from their staged copy
+fn open_destination(home_dir: &String, profile: &Profile) -> Result<(Destination, String), Error> {
+    let destination: Destination = Destination::select(home_dir, profile)?;
+    let backup_dir: String = destination.get_backup_dir(home_dir);
+    fs::create_dir_all(&backup_dir)?;
+    destination.fetch(&backup_dir)?;
+    Ok((destination, backup_dir))
+}
+
+fn get_backup_dir(home_dir: &String, profile: &Profile) -> Result<String, Error> {
+    Ok(open_destination(home_dir, profile)?.1)
+}
+
+fn main() -> Result<(), Error> {
+    let home_dir: Result<String, VarError> = env::var("HOME");
+    if home_dir.is_err() {
+        return Err(Error::other("HOME directory could not be determined"));
+    }
+    let home_dir: String = home_dir.unwrap();
+
+    let profile: Profile = Profile::read(&home_dir);
+
+    let args: Vec<String> = env::args().collect();
+    if args.len() == 1 {
+        return backup(&home_dir, &profile);
+    }
+
+    match args[1].as_str() {
+        "restore" => restore::run(&home_dir, &get_backup_dir(&home_dir, &profile)?),
+        "verify" => verify::run(&get_backup_dir(&home_dir, &profile)?),
+        "snapshots" => Repository::new(&get_backup_dir(&home_dir, &profile)?).print_snapshots(),
+        "prune" => {
+            let (destination, backup_dir): (Destination, String) =
+                open_destination(&home_dir, &profile)?;
+            prune(&backup_dir)?;
+            destination.sync(&backup_dir, true)
+        }
+        _ => {
+            print_help();
+            Ok(())
+        }
+    }
+}
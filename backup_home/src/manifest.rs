@@ -0,0 +1,243 @@
+use std::collections::HashMap;
+use std::fs;
+use std::fs::{DirEntry, Metadata, ReadDir};
+use std::io;
+use std::path::PathBuf;
+use std::time::UNIX_EPOCH;
+
+use crate::helper;
+
+const VCS_NAMES: [&str; 16] = [
+    ".git",
+    ".gitignore",
+    ".gitattributes",
+    ".gitmodules",
+    ".svn",
+    ".hg",
+    ".hgignore",
+    ".hgtags",
+    ".bzr",
+    ".bzrignore",
+    "CVS",
+    ".cvsignore",
+    "RCS",
+    "SCCS",
+    "_darcs",
+    ".arch-ids",
+];
+
+#[derive(Clone, PartialEq)]
+pub struct FileState {
+    pub mtime: u64,
+    pub size: u64,
+}
+
+pub struct Manifest {
+    pub files: HashMap<String, FileState>,
+    pub skipped: Vec<String>,
+}
+
+impl Manifest {
+    pub fn scan(home_dir: &String, folder: &String) -> Manifest {
+        let mut manifest: Manifest = Manifest {
+            files: HashMap::new(),
+            skipped: vec![],
+        };
+        manifest.scan_dir(home_dir, &format!("{}/{}", home_dir, folder));
+        manifest
+    }
+
+    fn scan_dir(&mut self, home_dir: &String, path: &String) {
+        let dir: io::Result<ReadDir> = fs::read_dir(path);
+        if let Err(err) = &dir {
+            // a missing folder would otherwise back up as an empty archive that looks fine
+            self.skipped.push(format!(
+                "{} ({})",
+                &path[home_dir.len() + 1..],
+                if err.kind() == io::ErrorKind::NotFound {
+                    "not found"
+                } else {
+                    "could not be read"
+                }
+            ));
+            return;
+        }
+
+        let dir: ReadDir = dir.unwrap();
+        for entry in dir {
+            if entry.is_err() {
+                continue;
+            }
+
+            let entry: DirEntry = entry.unwrap();
+            let file_name = entry.file_name();
+            let file_name: Option<&str> = file_name.to_str();
+            if file_name.is_none() || VCS_NAMES.contains(&file_name.unwrap()) {
+                continue;
+            }
+
+            let entry_path_buff: PathBuf = entry.path();
+            let entry_path: Option<&str> = entry_path_buff.to_str();
+            if entry_path.is_none() {
+                continue;
+            }
+            let entry_path: String = entry_path.unwrap().to_string();
+
+            let meta_data: io::Result<Metadata> = fs::symlink_metadata(&entry_path);
+            if meta_data.is_err() {
+                self.skipped.push(format!(
+                    "{} (could not be read)",
+                    &entry_path[home_dir.len() + 1..]
+                ));
+                continue;
+            }
+            let meta_data: Metadata = meta_data.unwrap();
+
+            let relative_path: &str = &entry_path[home_dir.len() + 1..];
+            if meta_data.is_dir() {
+                // folders without anything to back up would silently vanish from the archive
+                let found: usize = self.files.len() + self.skipped.len();
+                self.scan_dir(home_dir, &entry_path);
+                if self.files.len() + self.skipped.len() == found {
+                    self.skipped
+                        .push(format!("{}/ (empty folder)", relative_path));
+                }
+            } else if meta_data.is_symlink() {
+                self.skipped.push(format!("{} (symlink)", relative_path));
+            } else if !meta_data.is_file() {
+                self.skipped
+                    .push(format!("{} (special file)", relative_path));
+            } else {
+                let mtime: u64 = meta_data
+                    .modified()
+                    .ok()
+                    .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+                    .map(|d| d.as_secs())
+                    .unwrap_or(0);
+                self.files.insert(
+                    relative_path.to_string(),
+                    FileState {
+                        mtime,
+                        size: meta_data.len(),
+                    },
+                );
+            }
+        }
+    }
+
+    pub fn read(path: &String) -> Result<Manifest, io::Error> {
+        let mut files: HashMap<String, FileState> = HashMap::new();
+        for line in fs::read_to_string(path)?.lines() {
+            let parts: Vec<&str> = line.splitn(3, '\t').collect();
+            if parts.len() != 3 {
+                continue;
+            }
+            let mtime: u64 = parts[0].parse::<u64>().unwrap_or(0);
+            let size: u64 = parts[1].parse::<u64>().unwrap_or(0);
+            files.insert(helper::unescape_path(parts[2]), FileState { mtime, size });
+        }
+        Ok(Manifest {
+            files,
+            skipped: vec![],
+        })
+    }
+
+    pub fn write(&self, path: &String) -> Result<(), io::Error> {
+        let mut content: String = String::new();
+        for file in self.paths() {
+            let state: &FileState = &self.files[&file];
+            content.push_str(&format!(
+                "{}\t{}\t{}\n",
+                state.mtime,
+                state.size,
+                helper::escape_path(&file)
+            ));
+        }
+        fs::write(path, content)
+    }
+
+    pub fn paths(&self) -> Vec<String> {
+        let mut paths: Vec<String> = self.files.keys().cloned().collect();
+        paths.sort();
+        paths
+    }
+
+    pub fn changed_paths(&self, previous: &Manifest) -> Vec<String> {
+        let mut paths: Vec<String> = self
+            .files
+            .iter()
+            .filter(|(path, state)| previous.files.get(*path) != Some(state))
+            .map(|(path, _)| path.to_string())
+            .collect();
+        paths.sort();
+        paths
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::process;
+
+    fn get_test_dir(name: &str) -> String {
+        let dir: PathBuf = env::temp_dir().join(format!("backup_home_{}_{}", name, process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir.to_string_lossy().to_string()
+    }
+
+    #[test]
+    fn names_with_tabs_and_newlines_round_trip() {
+        let dir: String = get_test_dir("manifest_names");
+        let names: [&str; 4] = [
+            "Documents/plain.txt",
+            "Documents/tab\there.txt",
+            "Documents/new\nline.txt",
+            "Documents/back\\slash\\n.txt",
+        ];
+        let mut manifest: Manifest = Manifest {
+            files: HashMap::new(),
+            skipped: vec![],
+        };
+        for (idx, name) in names.iter().enumerate() {
+            manifest.files.insert(
+                name.to_string(),
+                FileState {
+                    mtime: idx as u64,
+                    size: 10,
+                },
+            );
+        }
+        let path: String = format!("{}/manifest", dir);
+        manifest.write(&path).unwrap();
+        assert_eq!(fs::read_to_string(&path).unwrap().lines().count(), 4);
+
+        let read: Manifest = Manifest::read(&path).unwrap();
+        assert_eq!(read.paths(), manifest.paths());
+        for name in names {
+            assert!(read.files[name] == manifest.files[name]);
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn missing_folder_is_skipped() {
+        let home_dir: String = get_test_dir("missing_folder");
+        let manifest: Manifest = Manifest::scan(&home_dir, &String::from("Documents"));
+        assert!(manifest.files.is_empty());
+        assert_eq!(manifest.skipped, ["Documents (not found)"]);
+        fs::remove_dir_all(&home_dir).unwrap();
+    }
+
+    #[test]
+    fn empty_folders_are_skipped() {
+        let home_dir: String = get_test_dir("empty_folder");
+        fs::create_dir_all(format!("{}/Documents/empty", home_dir)).unwrap();
+        fs::write(format!("{}/Documents/a.txt", home_dir), "a").unwrap();
+        let manifest: Manifest = Manifest::scan(&home_dir, &String::from("Documents"));
+        assert_eq!(manifest.paths(), ["Documents/a.txt"]);
+        assert_eq!(manifest.skipped, ["Documents/empty/ (empty folder)"]);
+        fs::remove_dir_all(&home_dir).unwrap();
+    }
+}
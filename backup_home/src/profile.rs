@@ -0,0 +1,52 @@
+use std::fs;
+
+use crate::volume;
+
+pub struct Profile {
+    pub destination: Option<String>,
+    pub volume_size: Option<u64>,
+    pub threads: Option<usize>,
+}
+
+impl Profile {
+    pub fn get_path(home_dir: &String) -> String {
+        format!("{}/.config/backup_home/profile", home_dir)
+    }
+
+    pub fn read(home_dir: &String) -> Profile {
+        let mut profile: Profile = Profile {
+            destination: None,
+            volume_size: None,
+            threads: None,
+        };
+
+        let content: Result<String, std::io::Error> =
+            fs::read_to_string(Profile::get_path(home_dir));
+        if content.is_err() {
+            return profile;
+        }
+
+        for line in content.unwrap().lines() {
+            let line: &str = line.trim();
+            if line.starts_with('#') {
+                continue;
+            }
+            let key_value: Option<(&str, &str)> = line.split_once('=');
+            if key_value.is_none() {
+                continue;
+            }
+            let (key, value): (&str, &str) = key_value.unwrap();
+            let value: String = value.trim().to_string();
+            if value.is_empty() {
+                continue;
+            }
+            match key.trim() {
+                "destination" => profile.destination = Some(value),
+                "volume_size" => profile.volume_size = volume::parse_volume_size(&value),
+                "threads" => profile.threads = value.parse::<usize>().ok().filter(|t| *t > 0),
+                _ => (),
+            }
+        }
+        profile
+    }
+}
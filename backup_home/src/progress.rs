@@ -0,0 +1,110 @@
+use rust_cli::ansi::Color;
+use rust_cli::ansi::Font;
+
+use std::io;
+use std::io::{Read, Write};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::helper;
+
+pub struct Progress {
+    total_files: usize,
+    total_bytes: u64,
+    files: usize,
+    bytes: u64,
+    last_draw: Option<Instant>,
+}
+
+impl Progress {
+    pub fn new(total_files: usize, total_bytes: u64) -> Progress {
+        Progress {
+            total_files,
+            total_bytes,
+            files: 0,
+            bytes: 0,
+            last_draw: None,
+        }
+    }
+
+    pub fn add_file(&mut self) {
+        self.files += 1;
+        self.draw(false);
+    }
+
+    pub fn add_bytes(&mut self, bytes: u64) {
+        self.bytes += bytes;
+        self.draw(false);
+    }
+
+    pub fn print_lines(&mut self, lines: &[String]) {
+        rust_cli::ansi::erase::line();
+        rust_cli::ansi::cursor::line_start();
+        for line in lines {
+            println!("{}", line);
+        }
+        self.draw(true);
+    }
+
+    pub fn finish(&mut self) {
+        self.draw(true);
+        println!();
+    }
+
+    fn draw(&mut self, force: bool) {
+        if !force
+            && self
+                .last_draw
+                .is_some_and(|t| t.elapsed() < Duration::from_millis(100))
+        {
+            return;
+        }
+        self.last_draw = Some(Instant::now());
+
+        let percent: f64 = if self.total_bytes == 0 {
+            1.0
+        } else {
+            (self.bytes as f64 / self.total_bytes as f64).min(1.0)
+        };
+
+        let percent_bar_len: usize = 30;
+        let percent_bar: usize = (percent * percent_bar_len as f64) as usize;
+
+        rust_cli::ansi::erase::line();
+        rust_cli::ansi::cursor::line_start();
+        print!(
+            "    [{}{}] {: >3}% {}{} / {}{} {}/{} files",
+            "#".repeat(percent_bar),
+            ".".repeat(percent_bar_len - percent_bar),
+            (percent * 100.0) as usize,
+            Color::Cyan.as_str(),
+            helper::get_size_string(self.bytes),
+            helper::get_size_string(self.total_bytes),
+            Font::Reset.as_str(),
+            self.files,
+            self.total_files
+        );
+        let _ = io::stdout().flush();
+    }
+}
+
+pub struct ProgressReader<'a, R: Read> {
+    inner: R,
+    progress: &'a Mutex<Progress>,
+}
+
+impl<'a, R: Read> ProgressReader<'a, R> {
+    pub fn new(inner: R, progress: &'a Mutex<Progress>) -> ProgressReader<'a, R> {
+        ProgressReader { inner, progress }
+    }
+}
+
+impl<R: Read> Read for ProgressReader<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let count: usize = self.inner.read(buf)?;
+        if let Ok(mut progress) = self.progress.lock() {
+            progress.add_bytes(count as u64);
+        }
+        Ok(count)
+    }
+}
@@ -0,0 +1,159 @@
+use serde::Serialize;
+use std::fs;
+use std::io;
+
+use crate::helper;
+
+#[derive(Serialize)]
+pub struct FolderReport {
+    pub folder: String,
+    pub format: String,
+    pub files: usize,
+    pub input_bytes: u64,
+    pub output_bytes: u64,
+    // None when nothing new had to be stored
+    pub compression_ratio: Option<f64>,
+    pub encrypted: bool,
+    pub duration_seconds: f64,
+    pub skipped: Vec<String>,
+    pub error: Option<String>,
+}
+
+impl FolderReport {
+    pub fn new(folder: &String, format: &str) -> FolderReport {
+        FolderReport {
+            folder: folder.to_string(),
+            format: format.to_string(),
+            files: 0,
+            input_bytes: 0,
+            output_bytes: 0,
+            compression_ratio: None,
+            encrypted: false,
+            duration_seconds: 0.0,
+            skipped: vec![],
+            error: None,
+        }
+    }
+
+    pub fn set_bytes(&mut self, input_bytes: u64, output_bytes: u64) {
+        self.input_bytes = input_bytes;
+        self.output_bytes = output_bytes;
+        self.compression_ratio = if output_bytes > 0 {
+            Some(input_bytes as f64 / output_bytes as f64)
+        } else {
+            None
+        };
+    }
+
+    fn get_ratio_string(&self) -> String {
+        match self.compression_ratio {
+            Some(ratio) => format!("{:.2}x", ratio),
+            None => String::from("—"),
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct Report {
+    pub timestamp: String,
+    pub backup_dir: String,
+    pub folders: Vec<FolderReport>,
+    pub sync_error: Option<String>,
+}
+
+impl Report {
+    fn to_markdown(&self) -> String {
+        let mut markdown: String = format!("# Backup Report {}\n\n", self.timestamp);
+        markdown.push_str(&format!("Destination: `{}`\n\n", self.backup_dir));
+        markdown.push_str(
+            "| Folder | Format | Files | Input | Output | Ratio | Encrypted | Duration | Skipped | Status |\n",
+        );
+        markdown.push_str("|---|---|---:|---:|---:|---:|---|---:|---:|---|\n");
+        for folder in &self.folders {
+            markdown.push_str(&format!(
+                "| {} | {} | {} | {} | {} | {} | {} | {:.1}s | {} | {} |\n",
+                folder.folder,
+                folder.format,
+                folder.files,
+                helper::get_size_string(folder.input_bytes),
+                helper::get_size_string(folder.output_bytes),
+                folder.get_ratio_string(),
+                if folder.encrypted { "yes" } else { "no" },
+                folder.duration_seconds,
+                folder.skipped.len(),
+                if folder.error.is_some() {
+                    "failed"
+                } else {
+                    "ok"
+                }
+            ));
+        }
+
+        if let Some(error) = &self.sync_error {
+            markdown.push_str(&format!("\n## Failed to sync\n\n{}\n", error));
+        }
+
+        for folder in &self.folders {
+            if let Some(error) = &folder.error {
+                markdown.push_str(&format!("\n## Failed {}\n\n{}\n", folder.folder, error));
+            }
+        }
+
+        for folder in &self.folders {
+            if folder.skipped.is_empty() {
+                continue;
+            }
+            markdown.push_str(&format!("\n## Skipped in {}\n\n", folder.folder));
+            for path in &folder.skipped {
+                markdown.push_str(&format!("- `{}`\n", path));
+            }
+        }
+        markdown
+    }
+
+    pub fn print(&self) {
+        println!();
+        print!("{}", self.to_markdown());
+    }
+
+    pub fn save(&self) -> Result<String, io::Error> {
+        let reports_dir: String = format!("{}/reports", self.backup_dir);
+        fs::create_dir_all(&reports_dir)?;
+
+        let report_file: String = format!("{}/{}_report", reports_dir, self.timestamp);
+        let json: String = serde_json::to_string_pretty(self).map_err(io::Error::other)?;
+        fs::write(format!("{}.json", report_file), json)?;
+        fs::write(format!("{}.md", report_file), self.to_markdown())?;
+        Ok(report_file)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ratio_of_compressed_folder() {
+        let mut folder: FolderReport = FolderReport::new(&String::from("Documents"), "archive");
+        folder.set_bytes(3000, 1000);
+        assert_eq!(folder.compression_ratio, Some(3.0));
+        assert_eq!(folder.get_ratio_string(), "3.00x");
+    }
+
+    #[test]
+    fn ratio_without_new_data() {
+        let mut folder: FolderReport = FolderReport::new(&String::from("Pictures"), "repository");
+        folder.set_bytes(3000, 0);
+        assert_eq!(folder.compression_ratio, None);
+        assert_eq!(folder.get_ratio_string(), "—");
+
+        let report: Report = Report {
+            timestamp: String::from("20250101_000000"),
+            backup_dir: String::from("/backups"),
+            folders: vec![folder],
+            sync_error: None,
+        };
+        assert!(report.to_markdown().contains("| — |"));
+        assert!(!report.to_markdown().contains("0.00x"));
+    }
+}
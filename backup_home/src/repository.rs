@@ -0,0 +1,614 @@
+use rust_cli::ansi::Color;
+use rust_cli::ansi::Font;
+
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, UNIX_EPOCH};
+
+use crate::archive::ArchiveStats;
+use crate::helper;
+use crate::manifest::{FileState, Manifest};
+use crate::progress::{Progress, ProgressReader};
+
+const MIN_CHUNK_SIZE: usize = 256 * 1024;
+const MAX_CHUNK_SIZE: usize = 4 * 1024 * 1024;
+const CHUNK_MASK: u64 = ((1 << 20) - 1) << 44;
+
+const fn get_gear_table() -> [u64; 256] {
+    let mut table: [u64; 256] = [0; 256];
+    let mut state: u64 = 0;
+    let mut i: usize = 0;
+    while i < 256 {
+        state = state.wrapping_add(0x9E3779B97F4A7C15);
+        let mut z: u64 = state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58476D1CE4E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D049BB133111EB);
+        table[i] = z ^ (z >> 31);
+        i += 1;
+    }
+    table
+}
+
+const GEAR: [u64; 256] = get_gear_table();
+
+struct Chunker<R: Read> {
+    reader: R,
+    buffer: Vec<u8>,
+    eof: bool,
+}
+
+impl<R: Read> Chunker<R> {
+    fn new(reader: R) -> Chunker<R> {
+        Chunker {
+            reader,
+            buffer: Vec::with_capacity(MAX_CHUNK_SIZE),
+            eof: false,
+        }
+    }
+
+    fn fill(&mut self) -> Result<(), io::Error> {
+        let mut buf: [u8; 64 * 1024] = [0; 64 * 1024];
+        while !self.eof && self.buffer.len() < MAX_CHUNK_SIZE {
+            let want: usize = (MAX_CHUNK_SIZE - self.buffer.len()).min(buf.len());
+            let count: usize = self.reader.read(&mut buf[..want])?;
+            if count == 0 {
+                self.eof = true;
+            }
+            self.buffer.extend_from_slice(&buf[..count]);
+        }
+        Ok(())
+    }
+
+    fn next_chunk(&mut self) -> Result<Option<Vec<u8>>, io::Error> {
+        self.fill()?;
+        if self.buffer.is_empty() {
+            return Ok(None);
+        }
+
+        let mut cut: usize = self.buffer.len();
+        if self.buffer.len() > MIN_CHUNK_SIZE {
+            let mut hash: u64 = 0;
+            for (i, b) in self.buffer.iter().enumerate() {
+                hash = (hash << 1).wrapping_add(GEAR[*b as usize]);
+                if i >= MIN_CHUNK_SIZE && hash & CHUNK_MASK == 0 {
+                    cut = i + 1;
+                    break;
+                }
+            }
+        }
+
+        let rest: Vec<u8> = self.buffer.split_off(cut);
+        Ok(Some(std::mem::replace(&mut self.buffer, rest)))
+    }
+}
+
+pub struct IndexEntry {
+    pub state: FileState,
+    pub chunks: Vec<String>,
+}
+
+pub struct Index {
+    pub files: HashMap<String, IndexEntry>,
+}
+
+impl Index {
+    fn read(path: &String) -> Result<Index, io::Error> {
+        let mut files: HashMap<String, IndexEntry> = HashMap::new();
+        for line in fs::read_to_string(path)?.lines() {
+            let parts: Vec<&str> = line.splitn(4, '\t').collect();
+            if parts.len() != 4 {
+                continue;
+            }
+            files.insert(
+                helper::unescape_path(parts[3]),
+                IndexEntry {
+                    state: FileState {
+                        mtime: parts[0].parse::<u64>().unwrap_or(0),
+                        size: parts[1].parse::<u64>().unwrap_or(0),
+                    },
+                    chunks: parts[2]
+                        .split(',')
+                        .filter(|c| !c.is_empty())
+                        .map(|c| c.to_string())
+                        .collect(),
+                },
+            );
+        }
+        Ok(Index { files })
+    }
+
+    fn write(&self, path: &String) -> Result<(), io::Error> {
+        let mut content: String = String::new();
+        for file in self.paths() {
+            let entry: &IndexEntry = &self.files[&file];
+            content.push_str(&format!(
+                "{}\t{}\t{}\t{}\n",
+                entry.state.mtime,
+                entry.state.size,
+                entry.chunks.join(","),
+                helper::escape_path(&file)
+            ));
+        }
+        fs::write(path, content)
+    }
+
+    pub fn paths(&self) -> Vec<String> {
+        let mut paths: Vec<String> = self.files.keys().cloned().collect();
+        paths.sort();
+        paths
+    }
+
+    pub fn size(&self) -> u64 {
+        self.files.values().map(|e| e.state.size).sum()
+    }
+}
+
+pub struct Repository {
+    dir: String,
+}
+
+impl Repository {
+    pub fn new(backup_dir: &String) -> Repository {
+        Repository {
+            dir: format!("{}/repository", backup_dir),
+        }
+    }
+
+    pub fn exists(&self) -> bool {
+        Path::new(&self.dir).is_dir()
+    }
+
+    fn get_chunk_path(&self, hash: &str) -> String {
+        format!("{}/chunks/{}/{}", self.dir, &hash[..2], hash)
+    }
+
+    fn get_index_path(&self, folder: &String, timestamp: &String) -> String {
+        format!("{}/snapshots/{}/{}.index", self.dir, folder, timestamp)
+    }
+
+    pub fn get_folders(&self) -> Result<Vec<String>, io::Error> {
+        let mut folders: Vec<String> = vec![];
+        let snapshots_dir: String = format!("{}/snapshots", self.dir);
+        if !Path::new(&snapshots_dir).is_dir() {
+            return Ok(folders);
+        }
+        for entry in fs::read_dir(snapshots_dir)? {
+            if entry.is_err() {
+                continue;
+            }
+            let entry_path: PathBuf = entry.unwrap().path();
+            if !entry_path.is_dir() {
+                continue;
+            }
+            if let Some(folder) = entry_path.file_name().and_then(|n| n.to_str()) {
+                folders.push(folder.to_string());
+            }
+        }
+        folders.sort();
+        Ok(folders)
+    }
+
+    pub fn get_snapshots(&self, folder: &String) -> Result<Vec<String>, io::Error> {
+        let mut snapshots: Vec<String> = vec![];
+        let folder_dir: String = format!("{}/snapshots/{}", self.dir, folder);
+        if !Path::new(&folder_dir).is_dir() {
+            return Ok(snapshots);
+        }
+        for entry in fs::read_dir(folder_dir)? {
+            if entry.is_err() {
+                continue;
+            }
+            let file_name = entry.unwrap().file_name();
+            if let Some(timestamp) = file_name.to_str().and_then(|n| n.strip_suffix(".index")) {
+                snapshots.push(timestamp.to_string());
+            }
+        }
+        snapshots.sort();
+        Ok(snapshots)
+    }
+
+    pub fn read_index(&self, folder: &String, timestamp: &String) -> Result<Index, io::Error> {
+        Index::read(&self.get_index_path(folder, timestamp))
+    }
+
+    fn write_chunk(&self, data: &[u8]) -> Result<(String, u64), io::Error> {
+        let hash: String = format!("{:x}", Sha256::digest(data));
+        let chunk_path: String = self.get_chunk_path(&hash);
+        if Path::new(&chunk_path).is_file() {
+            return Ok((hash, 0));
+        }
+
+        fs::create_dir_all(format!("{}/chunks/{}", self.dir, &hash[..2]))?;
+        let temp_path: String = format!("{}.tmp", chunk_path);
+        let mut encoder: GzEncoder<File> =
+            GzEncoder::new(File::create(&temp_path)?, Compression::default());
+        encoder.write_all(data)?;
+        encoder.finish()?.sync_all()?;
+        fs::rename(&temp_path, &chunk_path)?;
+        let written: u64 = fs::metadata(&chunk_path)?.len();
+        Ok((hash, written))
+    }
+
+    fn get_latest_index(&self, folder: &String) -> Result<Option<Index>, io::Error> {
+        match self.get_snapshots(folder)?.last() {
+            Some(last) => Ok(Some(self.read_index(folder, last)?)),
+            None => Ok(None),
+        }
+    }
+
+    pub fn get_changed_size(&self, folder: &String, manifest: &Manifest) -> Result<u64, io::Error> {
+        let previous: Option<Index> = self.get_latest_index(folder)?;
+        Ok(manifest
+            .files
+            .iter()
+            .filter(|(path, state)| {
+                previous
+                    .as_ref()
+                    .and_then(|p| p.files.get(*path))
+                    .is_none_or(|e| &e.state != *state)
+            })
+            .map(|(_, state)| state.size)
+            .sum())
+    }
+
+    pub fn backup(
+        &self,
+        home_dir: &String,
+        folder: &String,
+        timestamp: &String,
+        manifest: &Manifest,
+    ) -> Result<ArchiveStats, io::Error> {
+        let previous: Option<Index> = self.get_latest_index(folder)?;
+
+        let paths: Vec<String> = manifest.paths();
+        let total_bytes: u64 = manifest.files.values().map(|s| s.size).sum();
+        let progress: Mutex<Progress> = Mutex::new(Progress::new(paths.len(), total_bytes));
+
+        let mut stats: ArchiveStats = ArchiveStats {
+            files: 0,
+            bytes: 0,
+            skipped: vec![],
+            volumes: 0,
+            output_bytes: 0,
+        };
+        let mut new_chunks: usize = 0;
+        let mut new_bytes: u64 = 0;
+
+        let mut index: Index = Index {
+            files: HashMap::new(),
+        };
+        for path in paths {
+            let state: &FileState = &manifest.files[&path];
+
+            let unchanged: Option<&IndexEntry> = previous
+                .as_ref()
+                .and_then(|p| p.files.get(&path))
+                .filter(|e| &e.state == state);
+            if let Some(entry) = unchanged {
+                index.files.insert(
+                    path,
+                    IndexEntry {
+                        state: state.clone(),
+                        chunks: entry.chunks.clone(),
+                    },
+                );
+                if let Ok(mut progress) = progress.lock() {
+                    progress.add_bytes(state.size);
+                    progress.add_file();
+                }
+                stats.files += 1;
+                stats.bytes += state.size;
+                continue;
+            }
+
+            let file: io::Result<File> = File::open(format!("{}/{}", home_dir, path));
+            if file.is_err() {
+                if let Ok(mut progress) = progress.lock() {
+                    progress.add_bytes(state.size);
+                    progress.add_file();
+                }
+                stats.skipped.push(path);
+                continue;
+            }
+
+            let mut chunks: Vec<String> = vec![];
+            let mut size: u64 = 0;
+            let mut chunker = Chunker::new(ProgressReader::new(file.unwrap(), &progress));
+            while let Some(data) = chunker.next_chunk()? {
+                let (hash, written): (String, u64) = self.write_chunk(&data)?;
+                if written > 0 {
+                    new_chunks += 1;
+                    new_bytes += data.len() as u64;
+                    stats.output_bytes += written;
+                }
+                size += data.len() as u64;
+                chunks.push(hash);
+            }
+            if let Ok(mut progress) = progress.lock() {
+                progress.add_file();
+            }
+
+            index.files.insert(
+                path,
+                IndexEntry {
+                    state: FileState {
+                        mtime: state.mtime,
+                        size,
+                    },
+                    chunks,
+                },
+            );
+            stats.files += 1;
+            stats.bytes += size;
+        }
+        if let Ok(mut progress) = progress.lock() {
+            progress.finish();
+        }
+
+        fs::create_dir_all(format!("{}/snapshots/{}", self.dir, folder))?;
+        index.write(&self.get_index_path(folder, timestamp))?;
+
+        println!(
+            "    Stored {}{}{} of new data in {} chunks",
+            Color::Cyan.as_str(),
+            helper::get_size_string(new_bytes),
+            Font::Reset.as_str(),
+            new_chunks
+        );
+        Ok(stats)
+    }
+
+    pub fn restore_file(&self, entry: &IndexEntry, destination: &Path) -> Result<(), io::Error> {
+        let mut file: File = File::create(destination)?;
+        for hash in &entry.chunks {
+            let mut data: Vec<u8> = vec![];
+            GzDecoder::new(File::open(self.get_chunk_path(hash))?).read_to_end(&mut data)?;
+            if &format!("{:x}", Sha256::digest(&data)) != hash {
+                return Err(io::Error::other(format!("chunk {} is corrupt", hash)));
+            }
+            file.write_all(&data)?;
+        }
+        file.set_modified(UNIX_EPOCH + Duration::from_secs(entry.state.mtime))?;
+        Ok(())
+    }
+
+    pub fn prune(&self, keep: usize) -> Result<(), io::Error> {
+        let mut removed_snapshots: usize = 0;
+        let mut referenced: HashSet<String> = HashSet::new();
+        for folder in self.get_folders()? {
+            let snapshots: Vec<String> = self.get_snapshots(&folder)?;
+            let remove_count: usize = snapshots.len().saturating_sub(keep);
+            for (i, timestamp) in snapshots.iter().enumerate() {
+                if i < remove_count {
+                    fs::remove_file(self.get_index_path(&folder, timestamp))?;
+                    removed_snapshots += 1;
+                    continue;
+                }
+                for entry in self.read_index(&folder, timestamp)?.files.into_values() {
+                    referenced.extend(entry.chunks);
+                }
+            }
+        }
+
+        let mut removed_chunks: usize = 0;
+        let mut removed_bytes: u64 = 0;
+        let chunks_dir: String = format!("{}/chunks", self.dir);
+        if Path::new(&chunks_dir).is_dir() {
+            for prefix in fs::read_dir(chunks_dir)? {
+                for chunk in fs::read_dir(prefix?.path())? {
+                    let chunk_path: PathBuf = chunk?.path();
+                    let hash: Option<&str> = chunk_path.file_name().and_then(|n| n.to_str());
+                    if hash.is_some_and(|h| referenced.contains(h)) {
+                        continue;
+                    }
+                    removed_bytes += fs::metadata(&chunk_path).map(|m| m.len()).unwrap_or(0);
+                    fs::remove_file(&chunk_path)?;
+                    removed_chunks += 1;
+                }
+            }
+        }
+
+        println!(
+            "Removed {}{}{} snapshots and {}{}{} chunks ({} freed)",
+            Color::Cyan.as_str(),
+            removed_snapshots,
+            Font::Reset.as_str(),
+            Color::Cyan.as_str(),
+            removed_chunks,
+            Font::Reset.as_str(),
+            helper::get_size_string(removed_bytes)
+        );
+        Ok(())
+    }
+
+    pub fn print_snapshots(&self) -> Result<(), io::Error> {
+        for folder in self.get_folders()? {
+            println!(
+                "{}{}{}",
+                Color::Blue.as_str(),
+                &folder,
+                Font::Reset.as_str()
+            );
+            for timestamp in self.get_snapshots(&folder)? {
+                let index: Index = self.read_index(&folder, &timestamp)?;
+                println!(
+                    "    {} {: >8} files {}{: >10}{}",
+                    timestamp,
+                    index.files.len(),
+                    Color::Cyan.as_str(),
+                    helper::get_size_string(index.size()),
+                    Font::Reset.as_str()
+                );
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::process;
+
+    fn get_test_dir(name: &str) -> String {
+        let dir: PathBuf = env::temp_dir().join(format!("backup_home_{}_{}", name, process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir.to_string_lossy().to_string()
+    }
+
+    // xorshift, so the chunk boundaries are the same on every run
+    fn get_data(size: usize, seed: u64) -> Vec<u8> {
+        let mut state: u64 = seed;
+        (0..size)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                state as u8
+            })
+            .collect()
+    }
+
+    fn get_chunks(data: &[u8]) -> Vec<Vec<u8>> {
+        let mut chunker: Chunker<&[u8]> = Chunker::new(data);
+        let mut chunks: Vec<Vec<u8>> = vec![];
+        while let Some(chunk) = chunker.next_chunk().unwrap() {
+            chunks.push(chunk);
+        }
+        chunks
+    }
+
+    #[test]
+    fn chunks_stay_within_limits() {
+        let data: Vec<u8> = get_data(12 * 1024 * 1024, 1);
+        let chunks: Vec<Vec<u8>> = get_chunks(&data);
+        assert!(chunks.len() > 2);
+        for chunk in &chunks[..chunks.len() - 1] {
+            assert!(chunk.len() > MIN_CHUNK_SIZE);
+            assert!(chunk.len() <= MAX_CHUNK_SIZE);
+        }
+        assert_eq!(chunks.concat(), data);
+    }
+
+    #[test]
+    fn chunks_without_boundaries_are_cut_at_max() {
+        let data: Vec<u8> = vec![0; 2 * MAX_CHUNK_SIZE + 10];
+        let chunks: Vec<Vec<u8>> = get_chunks(&data);
+        assert_eq!(
+            chunks.iter().map(|c| c.len()).collect::<Vec<usize>>(),
+            [MAX_CHUNK_SIZE, MAX_CHUNK_SIZE, 10]
+        );
+    }
+
+    #[test]
+    fn small_files_are_one_chunk() {
+        assert_eq!(get_chunks(&[]).len(), 0);
+        let data: Vec<u8> = get_data(MIN_CHUNK_SIZE, 2);
+        assert_eq!(get_chunks(&data), [data]);
+    }
+
+    #[test]
+    fn boundaries_survive_an_insert() {
+        let data: Vec<u8> = get_data(12 * 1024 * 1024, 3);
+        let mut changed: Vec<u8> = data.clone();
+        changed.splice(1000..1000, get_data(100, 4));
+
+        let chunks: Vec<Vec<u8>> = get_chunks(&data);
+        let changed_chunks: Vec<Vec<u8>> = get_chunks(&changed);
+        assert_ne!(chunks[0], changed_chunks[0]);
+        assert_eq!(chunks[1..], changed_chunks[1..]);
+    }
+
+    #[test]
+    fn index_round_trips() {
+        let dir: String = get_test_dir("index");
+        let mut index: Index = Index {
+            files: HashMap::new(),
+        };
+        for (idx, name) in ["Music/a.mp3", "Music/tab\there.mp3", "Music/new\nline.mp3"]
+            .iter()
+            .enumerate()
+        {
+            index.files.insert(
+                name.to_string(),
+                IndexEntry {
+                    state: FileState {
+                        mtime: 100 + idx as u64,
+                        size: 5,
+                    },
+                    chunks: vec![format!("{:064}", idx), format!("{:064}", idx + 1)],
+                },
+            );
+        }
+        index.files.insert(
+            String::from("Music/empty.txt"),
+            IndexEntry {
+                state: FileState { mtime: 7, size: 0 },
+                chunks: vec![],
+            },
+        );
+        let path: String = format!("{}/1.index", dir);
+        index.write(&path).unwrap();
+
+        let read: Index = Index::read(&path).unwrap();
+        assert_eq!(read.paths(), index.paths());
+        for (path, entry) in &index.files {
+            assert!(read.files[path].state == entry.state);
+            assert_eq!(read.files[path].chunks, entry.chunks);
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn prune_keeps_shared_chunks() {
+        let home_dir: String = get_test_dir("prune");
+        let repository: Repository = Repository::new(&format!("{}/backups", home_dir));
+        let folder: String = String::from("Documents");
+        fs::create_dir_all(format!("{}/Documents", home_dir)).unwrap();
+        fs::write(format!("{}/Documents/same.txt", home_dir), "unchanged").unwrap();
+        fs::write(format!("{}/Documents/edit.txt", home_dir), "first").unwrap();
+
+        let first: Manifest = Manifest::scan(&home_dir, &folder);
+        repository
+            .backup(&home_dir, &folder, &String::from("1"), &first)
+            .unwrap();
+        fs::write(format!("{}/Documents/edit.txt", home_dir), "second").unwrap();
+        let mut second: Manifest = Manifest::scan(&home_dir, &folder);
+        // the mtime alone may not change within the same second
+        second.files.get_mut("Documents/edit.txt").unwrap().mtime += 1;
+        repository
+            .backup(&home_dir, &folder, &String::from("2"), &second)
+            .unwrap();
+
+        let old_chunk: String = repository
+            .read_index(&folder, &String::from("1"))
+            .unwrap()
+            .files["Documents/edit.txt"]
+            .chunks[0]
+            .to_string();
+        repository.prune(1).unwrap();
+        assert_eq!(repository.get_snapshots(&folder).unwrap(), ["2"]);
+        assert!(!Path::new(&repository.get_chunk_path(&old_chunk)).exists());
+
+        let index: Index = repository.read_index(&folder, &String::from("2")).unwrap();
+        for (name, content) in [("same.txt", "unchanged"), ("edit.txt", "second")] {
+            let restored: PathBuf = PathBuf::from(format!("{}/{}", home_dir, name));
+            repository
+                .restore_file(&index.files[&format!("Documents/{}", name)], &restored)
+                .unwrap();
+            assert_eq!(fs::read_to_string(&restored).unwrap(), content);
+        }
+        fs::remove_dir_all(&home_dir).unwrap();
+    }
+}
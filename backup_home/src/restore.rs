@@ -0,0 +1,403 @@
+use rust_cli::ansi::Color;
+use rust_cli::ansi::Font;
+use rust_cli::prompts::select::Select;
+use rust_cli::prompts::text::Text;
+
+use flate2::read::GzDecoder;
+use std::collections::{BTreeSet, HashMap};
+use std::fs;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+
+use crate::archive::ArchiveReader;
+use crate::helper;
+use crate::manifest::Manifest;
+use crate::repository::{Index, Repository};
+use crate::snapshot;
+use crate::snapshot::Snapshot;
+use crate::verify;
+use crate::volume;
+
+#[derive(Clone, Copy, PartialEq)]
+enum ConflictAction {
+    Overwrite,
+    Skip,
+    KeepBoth,
+}
+
+struct Restore {
+    target_dir: String,
+    paths: Vec<String>,
+    conflict_action: Option<ConflictAction>,
+    restored: HashMap<String, PathBuf>,
+}
+
+impl Restore {
+    fn is_selected(&self, path: &str) -> bool {
+        self.paths.is_empty()
+            || self
+                .paths
+                .iter()
+                .any(|p| path == p || path.starts_with(&format!("{}/", p)))
+    }
+
+    fn get_conflict_action(&mut self, path: &str) -> Result<ConflictAction, io::Error> {
+        if let Some(action) = self.conflict_action {
+            return Ok(action);
+        }
+
+        let options: Vec<&str> = vec![
+            "Overwrite",
+            "Skip",
+            "Keep both",
+            "Overwrite all",
+            "Skip all",
+            "Keep both for all",
+        ];
+        let selection = Select::new()
+            .title(format!("{} already exists", path))
+            .options(&options)
+            .run_select()?;
+        if selection.is_none() {
+            return Err(io::Error::other("restore cancelled"));
+        }
+        let selection: usize = selection.unwrap().0;
+
+        let action: ConflictAction = match selection % 3 {
+            0 => ConflictAction::Overwrite,
+            1 => ConflictAction::Skip,
+            _ => ConflictAction::KeepBoth,
+        };
+        if selection >= 3 {
+            self.conflict_action = Some(action);
+        }
+        Ok(action)
+    }
+
+    fn get_destination(&mut self, path: &String) -> Result<Option<PathBuf>, io::Error> {
+        if !self.is_selected(path) {
+            return Ok(None);
+        }
+
+        let mut destination: PathBuf = match self.restored.get(path) {
+            Some(destination) => destination.to_path_buf(),
+            None => Path::new(&self.target_dir).join(path),
+        };
+        if destination.exists() && !self.restored.contains_key(path) {
+            match self.get_conflict_action(path)? {
+                ConflictAction::Overwrite => (),
+                ConflictAction::Skip => {
+                    println!(
+                        "    {}Skipped{} {}",
+                        Color::Yellow.as_str(),
+                        Font::Reset.as_str(),
+                        path
+                    );
+                    return Ok(None);
+                }
+                ConflictAction::KeepBoth => destination = get_free_path(&destination),
+            }
+        }
+
+        if let Some(parent) = destination.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        Ok(Some(destination))
+    }
+
+    fn extract(&mut self, snapshot: &Snapshot, passphrase: &String) -> Result<(), io::Error> {
+        if volume::get_volumes(&snapshot.archive)?.len() > 1 {
+            println!("Verifying volumes of {}...", &snapshot.archive);
+            if !verify::check_checksum(&snapshot.archive)? {
+                return Err(io::Error::other(format!(
+                    "volumes of {} failed verification",
+                    &snapshot.archive
+                )));
+            }
+        }
+
+        let mut archive: tar::Archive<GzDecoder<ArchiveReader>> =
+            ArchiveReader::open(&snapshot.archive, passphrase)?.tar();
+        let extracted: Result<(), io::Error> = self.extract_entries(&mut archive);
+        archive.into_inner().into_inner().finish(extracted)
+    }
+
+    fn extract_entries(
+        &mut self,
+        archive: &mut tar::Archive<GzDecoder<ArchiveReader>>,
+    ) -> Result<(), io::Error> {
+        for entry in archive.entries()? {
+            let mut entry = entry?;
+            if !entry.header().entry_type().is_file() {
+                continue;
+            }
+
+            let path: PathBuf = entry.path()?.to_path_buf();
+            if path
+                .components()
+                .any(|c| !matches!(c, Component::Normal(_)))
+            {
+                continue;
+            }
+            let path: Option<&str> = path.to_str();
+            if path.is_none() {
+                continue;
+            }
+            let path: String = path.unwrap().to_string();
+
+            if let Some(destination) = self.get_destination(&path)? {
+                entry.unpack(&destination)?;
+                self.restored.insert(path, destination);
+            }
+        }
+        Ok(())
+    }
+
+    fn restore_repository(
+        &mut self,
+        repository: &Repository,
+        index: &Index,
+    ) -> Result<(), io::Error> {
+        for path in index.paths() {
+            if let Some(destination) = self.get_destination(&path)? {
+                repository.restore_file(&index.files[&path], &destination)?;
+                self.restored.insert(path, destination);
+            }
+        }
+        Ok(())
+    }
+
+    fn remove_deleted_files(&mut self, chain: &[&Snapshot]) -> Result<(), io::Error> {
+        let last: Manifest = Manifest::read(&chain[chain.len() - 1].manifest)?;
+        for (path, destination) in &self.restored {
+            if !last.files.contains_key(path) && destination.is_file() {
+                fs::remove_file(destination)?;
+            }
+        }
+        self.restored
+            .retain(|path, _| last.files.contains_key(path));
+        Ok(())
+    }
+}
+
+fn get_free_path(path: &Path) -> PathBuf {
+    let parent: &Path = path.parent().unwrap_or(Path::new(""));
+    let stem: String = path
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let extension: String = path
+        .extension()
+        .map(|e| format!(".{}", e.to_string_lossy()))
+        .unwrap_or_default();
+
+    let mut count: usize = 1;
+    loop {
+        let free_path: PathBuf = parent.join(format!("{} ({}){}", stem, count, extension));
+        if !free_path.exists() {
+            return free_path;
+        }
+        count += 1;
+    }
+}
+
+fn get_snapshot_string(snapshot: &Snapshot) -> String {
+    let volumes: Vec<String> = volume::get_volumes(&snapshot.archive).unwrap_or_default();
+    let size: u64 = volumes
+        .iter()
+        .map(|v| fs::metadata(v).map(|m| m.len()).unwrap_or(0))
+        .sum();
+
+    let mut details: Vec<String> = vec![];
+    if snapshot.is_encrypted() {
+        details.push("encrypted".to_string());
+    }
+    if volumes.len() > 1 {
+        details.push(format!("{} volumes", volumes.len()));
+    }
+
+    format!(
+        "{} {}{}{} {}{: >10}{}{}",
+        snapshot.timestamp,
+        Color::Cyan.as_str(),
+        snapshot.kind.as_str(),
+        Font::Reset.as_str(),
+        Color::Blue.as_str(),
+        helper::get_size_string(size),
+        Font::Reset.as_str(),
+        if details.is_empty() {
+            String::new()
+        } else {
+            format!(" ({})", details.join(", "))
+        }
+    )
+}
+
+fn select_paths(all_paths: Vec<String>) -> Result<Vec<String>, io::Error> {
+    let options: Vec<&str> = vec!["Everything", "Selected paths"];
+    let selection = Select::new()
+        .title("Select what to restore")
+        .options(&options)
+        .run_select()?;
+    if selection.is_none_or(|s| s.0 == 0) {
+        return Ok(vec![]);
+    }
+
+    let mut entries: BTreeSet<String> = BTreeSet::new();
+    for path in all_paths {
+        let components: Vec<&str> = path.splitn(3, '/').collect();
+        entries.insert(components[..components.len().min(2)].join("/"));
+    }
+    let entries: Vec<String> = entries.into_iter().collect();
+
+    let paths: Vec<String> = Select::new()
+        .title("Select paths to restore")
+        .options(&entries)
+        .run_multi_select()?
+        .iter()
+        .map(|t| t.1.to_string())
+        .collect();
+    if paths.is_empty() {
+        return Err(io::Error::other("no paths selected to restore"));
+    }
+    Ok(paths)
+}
+
+fn get_target_dir(home_dir: &String) -> Result<String, io::Error> {
+    let mut target_dir: String = Text::new(format!("Restore into directory ({}):", home_dir))
+        .required(false)
+        .run()?;
+    if target_dir.trim().is_empty() {
+        target_dir = home_dir.to_string();
+    }
+    fs::create_dir_all(&target_dir)?;
+    Ok(target_dir)
+}
+
+fn print_restored(restore: &Restore) {
+    println!(
+        "Restored {}{}{} files into {}",
+        Color::Cyan.as_str(),
+        restore.restored.len(),
+        Font::Reset.as_str(),
+        &restore.target_dir
+    );
+}
+
+fn run_repository(home_dir: &String, repository: &Repository) -> Result<(), io::Error> {
+    let folders: Vec<String> = repository.get_folders()?;
+    let folder = Select::new()
+        .title("Select folder to restore")
+        .options(&folders)
+        .run_select()?;
+    if folder.is_none() {
+        return Ok(());
+    }
+    let folder: String = folder.unwrap().1.to_string();
+
+    let mut snapshots: Vec<String> = repository.get_snapshots(&folder)?;
+    if snapshots.is_empty() {
+        return Err(io::Error::other("no snapshots found to restore"));
+    }
+    snapshots.reverse();
+
+    let timestamp = Select::new()
+        .title("Select snapshot to restore")
+        .options(&snapshots)
+        .run_select()?;
+    if timestamp.is_none() {
+        return Ok(());
+    }
+    let index: Index = repository.read_index(&folder, timestamp.unwrap().1)?;
+
+    let paths: Vec<String> = select_paths(index.paths())?;
+    let target_dir: String = get_target_dir(home_dir)?;
+
+    let mut restore: Restore = Restore {
+        target_dir,
+        paths,
+        conflict_action: None,
+        restored: HashMap::new(),
+    };
+    println!("Restoring {}...", &folder);
+    restore.restore_repository(repository, &index)?;
+
+    print_restored(&restore);
+    Ok(())
+}
+
+pub fn run(home_dir: &String, backup_dir: &String) -> Result<(), io::Error> {
+    let repository: Repository = Repository::new(backup_dir);
+    if repository.exists() {
+        let sources: Vec<&str> = vec!["Archives", "Repository"];
+        let source = Select::new()
+            .title("Select backup source")
+            .options(&sources)
+            .run_select()?;
+        if source.is_none() {
+            return Ok(());
+        }
+        if source.unwrap().0 == 1 {
+            return run_repository(home_dir, &repository);
+        }
+    }
+
+    let folders: Vec<String> = snapshot::get_folders(backup_dir)?;
+    let folder = Select::new()
+        .title("Select folder to restore")
+        .options(&folders)
+        .run_select()?;
+    if folder.is_none() {
+        return Ok(());
+    }
+    let folder: String = folder.unwrap().1.to_string();
+    let folder_dir: String = format!("{}/{}", backup_dir, folder);
+
+    let snapshots: Vec<Snapshot> = snapshot::get_snapshots(&folder_dir)?;
+    if snapshots.is_empty() {
+        return Err(io::Error::other("no snapshots found to restore"));
+    }
+
+    let options: Vec<String> = snapshots.iter().rev().map(get_snapshot_string).collect();
+    let selection = Select::new()
+        .title("Select snapshot to restore")
+        .options(&options)
+        .run_select()?;
+    if selection.is_none() {
+        return Ok(());
+    }
+    let idx: usize = snapshots.len() - 1 - selection.unwrap().0;
+
+    let chain: Option<Vec<&Snapshot>> = snapshot::get_chain(&snapshots, idx);
+    if chain.is_none() {
+        return Err(io::Error::other("no full snapshot found before selection"));
+    }
+    let chain: Vec<&Snapshot> = chain.unwrap();
+
+    let paths: Vec<String> =
+        select_paths(Manifest::read(&chain[chain.len() - 1].manifest)?.paths())?;
+    let target_dir: String = get_target_dir(home_dir)?;
+
+    let mut passphrase: String = String::new();
+    if chain.iter().any(|s| s.is_encrypted()) {
+        passphrase = Text::new("Decryption Passphrase:")
+            .required(true)
+            .secret(true)
+            .run()?;
+    }
+
+    let mut restore: Restore = Restore {
+        target_dir,
+        paths,
+        conflict_action: None,
+        restored: HashMap::new(),
+    };
+    for snapshot in &chain {
+        println!("Extracting {}...", &snapshot.archive);
+        restore.extract(snapshot, &passphrase)?;
+    }
+    restore.remove_deleted_files(&chain)?;
+
+    print_restored(&restore);
+    Ok(())
+}
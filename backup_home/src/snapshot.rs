@@ -0,0 +1,120 @@
+use regex::{Captures, Regex};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum SnapshotKind {
+    Full,
+    Incremental,
+}
+
+impl SnapshotKind {
+    pub fn as_str(&self) -> &str {
+        match self {
+            SnapshotKind::Full => "full",
+            SnapshotKind::Incremental => "incr",
+        }
+    }
+}
+
+pub struct Snapshot {
+    pub timestamp: String,
+    pub kind: SnapshotKind,
+    pub archive: String,
+    pub manifest: String,
+}
+
+impl Snapshot {
+    pub fn is_encrypted(&self) -> bool {
+        self.archive.ends_with(".gpg")
+    }
+}
+
+pub fn get_snapshots(folder_dir: &String) -> Result<Vec<Snapshot>, io::Error> {
+    let mut snapshots: Vec<Snapshot> = vec![];
+    if !Path::new(folder_dir).is_dir() {
+        return Ok(snapshots);
+    }
+
+    let file_name_regex: Regex =
+        Regex::new(r"^((\d{8}_\d{6})_(full|incr)\.tar\.gz(\.gpg)?)(\.part\d{2,})?$").unwrap();
+    for entry in fs::read_dir(folder_dir)? {
+        if entry.is_err() {
+            continue;
+        }
+        let file_name = entry.unwrap().file_name();
+        let file_name: Option<&str> = file_name.to_str();
+        if file_name.is_none() {
+            continue;
+        }
+        let file_name: &str = file_name.unwrap();
+
+        let captures: Option<Captures> = file_name_regex.captures(file_name);
+        if captures.is_none() {
+            continue;
+        }
+        let captures: Captures = captures.unwrap();
+
+        let archive: String = format!("{}/{}", folder_dir, &captures[1]);
+        if snapshots.iter().any(|s| s.archive == archive) {
+            continue;
+        }
+
+        let timestamp: String = captures[2].to_string();
+        let manifest: String = format!("{}/{}.manifest", folder_dir, timestamp);
+        if !Path::new(&manifest).is_file() {
+            continue;
+        }
+
+        snapshots.push(Snapshot {
+            kind: match &captures[3] {
+                "full" => SnapshotKind::Full,
+                _ => SnapshotKind::Incremental,
+            },
+            archive,
+            manifest,
+            timestamp,
+        });
+    }
+
+    snapshots.sort_by(|a, b| a.timestamp.cmp(&b.timestamp));
+    Ok(snapshots)
+}
+
+pub fn get_chain(snapshots: &[Snapshot], idx: usize) -> Option<Vec<&Snapshot>> {
+    let mut chain: Vec<&Snapshot> = vec![];
+    for snapshot in snapshots[..=idx].iter().rev() {
+        chain.push(snapshot);
+        if snapshot.kind == SnapshotKind::Full {
+            chain.reverse();
+            return Some(chain);
+        }
+    }
+    None
+}
+
+pub fn get_folders(backup_dir: &String) -> Result<Vec<String>, io::Error> {
+    let mut folders: Vec<String> = vec![];
+    for entry in fs::read_dir(backup_dir)? {
+        if entry.is_err() {
+            continue;
+        }
+        let entry_path: PathBuf = entry.unwrap().path();
+        if !entry_path.is_dir() {
+            continue;
+        }
+        let folder_dir: Option<&str> = entry_path.to_str();
+        if folder_dir.is_none() {
+            continue;
+        }
+        if get_snapshots(&folder_dir.unwrap().to_string())?.is_empty() {
+            continue;
+        }
+        if let Some(folder) = entry_path.file_name().and_then(|n| n.to_str()) {
+            folders.push(folder.to_string());
+        }
+    }
+    folders.sort();
+    Ok(folders)
+}
@@ -0,0 +1,121 @@
+use rust_cli::ansi::Color;
+use rust_cli::ansi::Font;
+
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::path::Path;
+
+use crate::archive;
+use crate::archive::ArchiveStats;
+use crate::snapshot;
+use crate::snapshot::Snapshot;
+use crate::volume;
+
+fn get_checksum(file: &String) -> Result<String, io::Error> {
+    let mut hasher: Sha256 = Sha256::new();
+    io::copy(&mut File::open(file)?, &mut hasher)?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+fn get_file_name(file: &String) -> &str {
+    Path::new(file)
+        .file_name()
+        .and_then(|n| n.to_str())
+        .unwrap_or(file)
+}
+
+pub fn write_checksum(archive: &String) -> Result<(), io::Error> {
+    let mut content: String = String::new();
+    for volume in volume::get_volumes(archive)? {
+        content.push_str(&format!(
+            "{}  {}\n",
+            get_checksum(&volume)?,
+            get_file_name(&volume)
+        ));
+    }
+    fs::write(format!("{}.sha256", archive), content)
+}
+
+pub fn check_checksum(archive: &String) -> Result<bool, io::Error> {
+    let sidecar: String = fs::read_to_string(format!("{}.sha256", archive))?;
+    let mut expected: HashMap<&str, &str> = HashMap::new();
+    for line in sidecar.lines() {
+        if let Some((checksum, file_name)) = line.split_once("  ") {
+            expected.insert(file_name, checksum);
+        }
+    }
+
+    let volumes: Vec<String> = volume::get_volumes(archive)?;
+    if volumes.len() != expected.len() {
+        return Ok(false);
+    }
+    for volume in volumes {
+        match expected.get(get_file_name(&volume)) {
+            Some(checksum) if *checksum == get_checksum(&volume)? => (),
+            _ => return Ok(false),
+        }
+    }
+    Ok(true)
+}
+
+pub fn check_archive(
+    archive: &String,
+    passphrase: &String,
+    stats: &ArchiveStats,
+) -> Result<(), io::Error> {
+    let (files, bytes): (usize, u64) = archive::list(archive, passphrase)?;
+    if files != stats.files || bytes != stats.bytes {
+        return Err(io::Error::other(format!(
+            "archive {} has {} files ({} bytes), expected {} files ({} bytes)",
+            archive, files, bytes, stats.files, stats.bytes
+        )));
+    }
+    Ok(())
+}
+
+pub fn run(backup_dir: &String) -> Result<(), io::Error> {
+    let mut failed: usize = 0;
+    let mut checked: usize = 0;
+
+    for folder in snapshot::get_folders(backup_dir)? {
+        let snapshots: Vec<Snapshot> =
+            snapshot::get_snapshots(&format!("{}/{}", backup_dir, folder))?;
+        for snapshot in snapshots {
+            checked += 1;
+            print!("{} ", &snapshot.archive);
+            match check_checksum(&snapshot.archive) {
+                Ok(true) => println!("{}OK{}", Color::Green.as_str(), Font::Reset.as_str()),
+                Ok(false) => {
+                    failed += 1;
+                    println!("{}FAILED{}", Color::Red.as_str(), Font::Reset.as_str());
+                }
+                Err(e) => {
+                    failed += 1;
+                    println!(
+                        "{}ERROR{} ({})",
+                        Color::Yellow.as_str(),
+                        Font::Reset.as_str(),
+                        e
+                    );
+                }
+            }
+        }
+    }
+
+    println!(
+        "Verified {}{}{} archives, {}{}{} failed",
+        Color::Cyan.as_str(),
+        checked,
+        Font::Reset.as_str(),
+        Color::Cyan.as_str(),
+        failed,
+        Font::Reset.as_str()
+    );
+    if failed > 0 {
+        return Err(io::Error::other("some archives failed verification"));
+    }
+    Ok(())
+}
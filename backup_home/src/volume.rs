@@ -0,0 +1,246 @@
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::io::{Read, Write};
+use std::path::Path;
+
+use crate::helper;
+
+// anything smaller ends up as thousands of part files
+pub const MIN_VOLUME_SIZE: u64 = 1024 * 1024;
+
+pub fn parse_volume_size(size: &str) -> Option<u64> {
+    helper::parse_size(size).filter(|s| *s >= MIN_VOLUME_SIZE)
+}
+
+fn get_volume_path(archive: &String, index: usize) -> String {
+    format!("{}.part{:02}", archive, index)
+}
+
+pub fn get_volumes(archive: &String) -> Result<Vec<String>, io::Error> {
+    if Path::new(archive).is_file() {
+        return Ok(vec![archive.to_string()]);
+    }
+
+    let path: &Path = Path::new(archive);
+    let prefix: String = format!(
+        "{}.part",
+        path.file_name()
+            .and_then(|n| n.to_str())
+            .ok_or(io::Error::other("invalid archive name"))?
+    );
+
+    let mut numbers: Vec<usize> = vec![];
+    for entry in fs::read_dir(path.parent().unwrap_or(Path::new(".")))? {
+        let file_name = entry?.file_name();
+        let number: Option<usize> = file_name
+            .to_str()
+            .and_then(|n| n.strip_prefix(&prefix))
+            .and_then(|n| n.parse::<usize>().ok());
+        if let Some(number) = number {
+            numbers.push(number);
+        }
+    }
+    numbers.sort();
+
+    if numbers.is_empty() {
+        return Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("archive {} not found", archive),
+        ));
+    }
+    for (index, number) in numbers.iter().enumerate() {
+        if index != *number {
+            return Err(io::Error::other(format!(
+                "volume {} is missing",
+                get_volume_path(archive, index)
+            )));
+        }
+    }
+
+    Ok(numbers
+        .iter()
+        .map(|n| get_volume_path(archive, *n))
+        .collect())
+}
+
+pub struct VolumeWriter {
+    archive: String,
+    volume_size: Option<u64>,
+    file: File,
+    written: u64,
+    volumes: usize,
+}
+
+impl VolumeWriter {
+    pub fn create(archive: &String, volume_size: Option<u64>) -> Result<VolumeWriter, io::Error> {
+        let first: String = match volume_size {
+            Some(_) => get_volume_path(archive, 0),
+            None => archive.to_string(),
+        };
+        Ok(VolumeWriter {
+            archive: archive.to_string(),
+            volume_size,
+            file: File::create(first)?,
+            written: 0,
+            volumes: 1,
+        })
+    }
+
+    pub fn finish(self) -> Result<usize, io::Error> {
+        self.file.sync_all()?;
+        Ok(self.volumes)
+    }
+}
+
+impl Write for VolumeWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.volume_size.is_none() {
+            return self.file.write(buf);
+        }
+        let volume_size: u64 = self.volume_size.unwrap();
+
+        if self.written >= volume_size {
+            self.file.sync_all()?;
+            self.file = File::create(get_volume_path(&self.archive, self.volumes))?;
+            self.volumes += 1;
+            self.written = 0;
+        }
+
+        let count: usize = buf.len().min((volume_size - self.written) as usize);
+        let count: usize = self.file.write(&buf[..count])?;
+        self.written += count as u64;
+        Ok(count)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+pub struct VolumeReader {
+    volumes: Vec<String>,
+    index: usize,
+    file: Option<File>,
+}
+
+impl VolumeReader {
+    pub fn open(archive: &String) -> Result<VolumeReader, io::Error> {
+        Ok(VolumeReader {
+            volumes: get_volumes(archive)?,
+            index: 0,
+            file: None,
+        })
+    }
+}
+
+impl Read for VolumeReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            if self.file.is_none() {
+                if self.index >= self.volumes.len() {
+                    return Ok(0);
+                }
+                self.file = Some(File::open(&self.volumes[self.index])?);
+            }
+
+            let count: usize = self.file.as_mut().unwrap().read(buf)?;
+            if count > 0 || buf.is_empty() {
+                return Ok(count);
+            }
+            self.file = None;
+            self.index += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::path::PathBuf;
+    use std::process;
+
+    fn get_test_dir(name: &str) -> PathBuf {
+        let dir: PathBuf = env::temp_dir().join(format!("backup_home_{}_{}", name, process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn volume_size_has_a_minimum() {
+        assert_eq!(parse_volume_size("1M"), Some(MIN_VOLUME_SIZE));
+        assert_eq!(parse_volume_size("4G"), Some(4 * 1024 * 1024 * 1024));
+        assert_eq!(parse_volume_size("512K"), None);
+        assert_eq!(parse_volume_size("100"), None);
+    }
+
+    #[test]
+    fn volumes_round_trip_across_a_boundary() {
+        let dir: PathBuf = get_test_dir("round_trip");
+        let archive: String = dir.join("backup.tar.gz").to_string_lossy().to_string();
+        let data: Vec<u8> = (0..25).collect();
+
+        let mut writer: VolumeWriter = VolumeWriter::create(&archive, Some(10)).unwrap();
+        writer.write_all(&data).unwrap();
+        assert_eq!(writer.finish().unwrap(), 3);
+        assert_eq!(
+            get_volumes(&archive).unwrap(),
+            vec![
+                format!("{}.part00", archive),
+                format!("{}.part01", archive),
+                format!("{}.part02", archive),
+            ]
+        );
+        assert_eq!(
+            fs::metadata(format!("{}.part01", archive)).unwrap().len(),
+            10
+        );
+
+        let mut read: Vec<u8> = vec![];
+        VolumeReader::open(&archive)
+            .unwrap()
+            .read_to_end(&mut read)
+            .unwrap();
+        assert_eq!(read, data);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn single_file_without_volume_size() {
+        let dir: PathBuf = get_test_dir("single");
+        let archive: String = dir.join("backup.tar.gz").to_string_lossy().to_string();
+
+        let mut writer: VolumeWriter = VolumeWriter::create(&archive, None).unwrap();
+        writer.write_all(b"plain").unwrap();
+        assert_eq!(writer.finish().unwrap(), 1);
+        assert_eq!(get_volumes(&archive).unwrap(), vec![archive.to_string()]);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn missing_volume_is_reported() {
+        let dir: PathBuf = get_test_dir("gap");
+        let archive: String = dir.join("backup.tar.gz").to_string_lossy().to_string();
+        fs::write(format!("{}.part00", archive), b"a").unwrap();
+        fs::write(format!("{}.part02", archive), b"c").unwrap();
+
+        let err: io::Error = get_volumes(&archive).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            format!("volume {}.part01 is missing", archive)
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn missing_archive_is_not_found() {
+        let dir: PathBuf = get_test_dir("missing");
+        let archive: String = dir.join("backup.tar.gz").to_string_lossy().to_string();
+
+        let err: io::Error = get_volumes(&archive).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,86 @@
+use image::DynamicImage;
+use std::fs;
+use std::io;
+
+use crate::image_file;
+use crate::metadata::Metadata;
+use crate::options::Options;
+use crate::plan::Job;
+
+pub enum Failure {
+    Decode(io::Error),
+    Encode(io::Error),
+}
+
+impl Failure {
+    pub fn into_error(self) -> io::Error {
+        match self {
+            Failure::Decode(err) => io::Error::other(format!("could not decode: {}", err)),
+            Failure::Encode(err) => io::Error::other(format!("could not encode: {}", err)),
+        }
+    }
+}
+
+pub struct Encoded {
+    pub image: DynamicImage,
+    pub data: Vec<u8>,
+    pub quality: u8,
+    pub scaled: bool,
+}
+
+pub struct Change {
+    pub decoded: bool,
+    pub dimensions: Option<(u32, u32)>,
+    pub resized: bool,
+    pub strip_gps: bool,
+    // None when the file is kept as it is
+    pub encoded: Option<Encoded>,
+}
+
+// both a real run and a dry run decide here, so they cannot disagree
+pub fn get_change(job: &Job, options: &Options) -> Result<Change, Failure> {
+    let mut change: Change = Change {
+        decoded: false,
+        dimensions: None,
+        resized: false,
+        strip_gps: false,
+        encoded: None,
+    };
+    let too_big: bool = options
+        .target_size
+        .is_some_and(|t| fs::metadata(&job.file_path).is_ok_and(|m| m.len() > t));
+    if !job.needs_convert && job.max_size.is_none() && !options.strip_gps && !too_big {
+        change.dimensions = image_file::read_dimensions(&job.file_path, job.file_type);
+        return Ok(change);
+    }
+
+    change.decoded = true;
+    let (mut image, metadata): (DynamicImage, Metadata) =
+        image_file::decode(&job.file_path, job.file_type).map_err(Failure::Decode)?;
+    change.dimensions = Some((image.width(), image.height()));
+    if let Some(small_image) = job.max_size.and_then(|s| image_file::resize(&image, s)) {
+        image = small_image;
+        change.resized = true;
+    }
+    change.strip_gps = options.strip_gps && metadata.has_gps();
+    if !job.needs_convert && !change.resized && !change.strip_gps && !too_big {
+        return Ok(change);
+    }
+
+    let (data, quality, scaled): (Vec<u8>, u8, Option<DynamicImage>) = image_file::encode_to_size(
+        &image,
+        job.format,
+        job.quality,
+        &metadata,
+        options.strip_gps,
+        options.target_size,
+    )
+    .map_err(Failure::Encode)?;
+    change.encoded = Some(Encoded {
+        scaled: scaled.is_some(),
+        image: scaled.unwrap_or(image),
+        data,
+        quality,
+    });
+    Ok(change)
+}
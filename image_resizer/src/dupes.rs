@@ -0,0 +1,198 @@
+use rust_cli::ansi::Color;
+use rust_cli::ansi::Font;
+use rust_cli::prompts::confirm::Confirm;
+
+use image::{DynamicImage, GrayImage};
+use std::collections::hash_map::DefaultHasher;
+use std::fs;
+use std::hash::Hasher;
+use std::io;
+
+use crate::file_type;
+use crate::file_type::FileType;
+use crate::helper;
+use crate::image_file;
+use crate::options::Options;
+use crate::pool;
+use crate::trash::Trash;
+
+struct Photo {
+    file_path: String,
+    width: u32,
+    height: u32,
+    size: u64,
+    content_hash: u64,
+    image_hash: u64,
+}
+
+impl Photo {
+    fn read(file_path: &String) -> Result<Option<Photo>, io::Error> {
+        let file_type: Option<FileType> = file_type::detect(file_path)?;
+        if file_type.is_none() {
+            return Ok(None);
+        }
+
+        let content: Vec<u8> = fs::read(file_path)?;
+        let mut hasher: DefaultHasher = DefaultHasher::new();
+        hasher.write(&content);
+
+        let (image, _) = image_file::decode(file_path, file_type.unwrap())?;
+        Ok(Some(Photo {
+            file_path: file_path.to_string(),
+            width: image.width(),
+            height: image.height(),
+            size: content.len() as u64,
+            content_hash: hasher.finish(),
+            image_hash: get_image_hash(&image),
+        }))
+    }
+
+    fn pixels(&self) -> u64 {
+        self.width as u64 * self.height as u64
+    }
+}
+
+// difference hash, each bit says if a pixel is darker than its right neighbour
+fn get_image_hash(image: &DynamicImage) -> u64 {
+    let small: GrayImage = image.thumbnail_exact(9, 8).to_luma8();
+    let mut hash: u64 = 0;
+    for y in 0..8 {
+        for x in 0..8 {
+            hash <<= 1;
+            if small.get_pixel(x, y)[0] < small.get_pixel(x + 1, y)[0] {
+                hash |= 1;
+            }
+        }
+    }
+    hash
+}
+
+fn is_dupe(a: &Photo, b: &Photo, distance: u32) -> bool {
+    let same: bool = a.content_hash == b.content_hash && a.size == b.size;
+    same || (a.image_hash ^ b.image_hash).count_ones() <= distance
+}
+
+fn get_groups(photos: &[Photo], distance: u32) -> Vec<Vec<&Photo>> {
+    let mut photos: Vec<&Photo> = photos.iter().collect();
+    // highest resolution first, then the larger file
+    photos.sort_by(|a, b| {
+        b.pixels()
+            .cmp(&a.pixels())
+            .then(b.size.cmp(&a.size))
+            .then(a.file_path.cmp(&b.file_path))
+    });
+
+    // every photo is compared with the one that is kept, so bursts do not chain together
+    let mut grouped: Vec<bool> = vec![false; photos.len()];
+    let mut groups: Vec<Vec<&Photo>> = vec![];
+    for keep in 0..photos.len() {
+        if grouped[keep] {
+            continue;
+        }
+        let mut group: Vec<&Photo> = vec![photos[keep]];
+        for extra in keep + 1..photos.len() {
+            if !grouped[extra] && is_dupe(photos[keep], photos[extra], distance) {
+                grouped[extra] = true;
+                group.push(photos[extra]);
+            }
+        }
+        if group.len() > 1 {
+            groups.push(group);
+        }
+    }
+    groups.sort_by(|a, b| a[0].file_path.cmp(&b[0].file_path));
+    groups
+}
+
+fn print_group(group: &[&Photo]) {
+    let exact: bool = group
+        .iter()
+        .all(|p| p.content_hash == group[0].content_hash && p.size == group[0].size);
+    println!(
+        "{}{}{}",
+        Color::Cyan.as_str(),
+        if exact {
+            "Exact duplicates"
+        } else {
+            "Similar photos"
+        },
+        Font::Reset.as_str()
+    );
+    for (idx, photo) in group.iter().enumerate() {
+        println!(
+            "    {}{: <5}{} {: >11} {: >10}  {}",
+            if idx == 0 {
+                Color::Green.as_str()
+            } else {
+                Color::Yellow.as_str()
+            },
+            if idx == 0 { "keep" } else { "extra" },
+            Font::Reset.as_str(),
+            format!("{}x{}", photo.width, photo.height),
+            helper::get_size_string(photo.size),
+            photo.file_path
+        );
+    }
+}
+
+pub fn run(options: &Options, files: &[String], trash: Option<&Trash>) -> Result<(), io::Error> {
+    let photos: Vec<Option<Photo>> = pool::run(files, options.threads, |file_path, log| {
+        match Photo::read(file_path) {
+            Ok(photo) => photo,
+            Err(err) => {
+                log.push(format!(
+                    "{}Failed{} to read {}: {}",
+                    Color::Red.as_str(),
+                    Font::Reset.as_str(),
+                    file_path,
+                    err
+                ));
+                None
+            }
+        }
+    });
+    let photos: Vec<Photo> = photos.into_iter().flatten().collect();
+
+    let groups: Vec<Vec<&Photo>> = get_groups(&photos, options.distance);
+    for group in &groups {
+        print_group(group);
+    }
+
+    let extras: Vec<&Photo> = groups.iter().flat_map(|g| g[1..].to_vec()).collect();
+    println!(
+        "Found {}{}{} groups of dupes in {} photos, {} extra copies use {}",
+        Color::Cyan.as_str(),
+        groups.len(),
+        Font::Reset.as_str(),
+        photos.len(),
+        extras.len(),
+        helper::get_size_string(extras.iter().map(|p| p.size).sum())
+    );
+
+    if trash.is_some() && options.dry_run {
+        println!("Would move {} extra copies to the Trash", extras.len());
+        return Ok(());
+    }
+    if let Some(trash) = trash {
+        if extras.is_empty()
+            || !Confirm::new(format!("Move {} extra copies to the Trash?", extras.len()))
+                .default_no(true)
+                .run()?
+        {
+            return Ok(());
+        }
+        for photo in &extras {
+            if let Err(err) = trash.put(&photo.file_path) {
+                println!(
+                    "{}Failed{} to trash {}: {}",
+                    Color::Red.as_str(),
+                    Font::Reset.as_str(),
+                    &photo.file_path,
+                    err
+                );
+            }
+        }
+        println!("Moved {} extra copies to the Trash", extras.len());
+    }
+    Ok(())
+}
@@ -0,0 +1,133 @@
+use image::ImageFormat;
+use std::fs::File;
+use std::io;
+use std::io::Read;
+use std::path::Path;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum FileType {
+    Jpeg,
+    Png,
+    Gif,
+    Bmp,
+    Tiff,
+    Webp,
+    Heic,
+    Avif,
+    Raw,
+}
+
+const RAW_EXTENSIONS: [&str; 7] = ["cr2", "nef", "nrw", "dng", "arw", "pef", "srw"];
+
+const MP4_BRANDS: [&[u8; 4]; 12] = [
+    b"isom", b"iso2", b"iso4", b"iso5", b"iso6", b"mp41", b"mp42", b"avc1", b"M4V ", b"3gp4",
+    b"3gp5", b"3g2a",
+];
+
+impl FileType {
+    pub fn as_str(&self) -> &str {
+        match self {
+            FileType::Jpeg => "JPEG",
+            FileType::Png => "PNG",
+            FileType::Gif => "GIF",
+            FileType::Bmp => "BMP",
+            FileType::Tiff => "TIFF",
+            FileType::Webp => "WebP",
+            FileType::Heic => "HEIC",
+            FileType::Avif => "AVIF",
+            FileType::Raw => "RAW",
+        }
+    }
+
+    pub fn image_format(&self) -> Option<ImageFormat> {
+        match self {
+            FileType::Jpeg => Some(ImageFormat::Jpeg),
+            FileType::Png => Some(ImageFormat::Png),
+            FileType::Gif => Some(ImageFormat::Gif),
+            FileType::Bmp => Some(ImageFormat::Bmp),
+            FileType::Tiff => Some(ImageFormat::Tiff),
+            FileType::Webp => Some(ImageFormat::WebP),
+            FileType::Heic | FileType::Avif | FileType::Raw => None,
+        }
+    }
+}
+
+fn get_ftyp_brand(header: &[u8]) -> Option<&[u8]> {
+    if header.len() >= 12 && &header[4..8] == b"ftyp" {
+        return Some(&header[8..12]);
+    }
+    None
+}
+
+fn has_raw_extension(file: &str) -> bool {
+    Path::new(file)
+        .extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| RAW_EXTENSIONS.contains(&e.to_lowercase().as_str()))
+}
+
+fn read_header(file: &String) -> Result<Vec<u8>, io::Error> {
+    let mut header: Vec<u8> = Vec::with_capacity(16);
+    File::open(file)?.take(16).read_to_end(&mut header)?;
+    Ok(header)
+}
+
+pub fn detect(file: &String) -> Result<Option<FileType>, io::Error> {
+    let header: Vec<u8> = read_header(file)?;
+
+    if header.starts_with(&[0xFF, 0xD8, 0xFF]) {
+        return Ok(Some(FileType::Jpeg));
+    }
+    if header.starts_with(&[0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A]) {
+        return Ok(Some(FileType::Png));
+    }
+    if header.starts_with(b"GIF87a") || header.starts_with(b"GIF89a") {
+        return Ok(Some(FileType::Gif));
+    }
+    if header.starts_with(b"BM") {
+        return Ok(Some(FileType::Bmp));
+    }
+    if header.starts_with(b"II*\0") && header.len() >= 10 && &header[8..10] == b"CR" {
+        return Ok(Some(FileType::Raw));
+    }
+    if header.starts_with(b"IIU\0")
+        || header.starts_with(b"IIRO")
+        || header.starts_with(b"IIRS")
+        || header.starts_with(b"FUJIFILMCCD-RAW")
+    {
+        return Ok(Some(FileType::Raw));
+    }
+    if header.starts_with(b"II*\0") || header.starts_with(b"MM\0*") {
+        // NEF, DNG, ARW and friends are plain TIFF containers
+        if has_raw_extension(file) {
+            return Ok(Some(FileType::Raw));
+        }
+        return Ok(Some(FileType::Tiff));
+    }
+    if header.len() >= 12 && header.starts_with(b"RIFF") && &header[8..12] == b"WEBP" {
+        return Ok(Some(FileType::Webp));
+    }
+    match get_ftyp_brand(&header) {
+        Some(b"heic") | Some(b"heix") | Some(b"hevc") | Some(b"mif1") => Ok(Some(FileType::Heic)),
+        Some(b"avif") | Some(b"avis") => Ok(Some(FileType::Avif)),
+        Some(b"crx ") => Ok(Some(FileType::Raw)),
+        _ => Ok(None),
+    }
+}
+
+// videos are written back into the same container, so this returns the ffmpeg muxer
+pub fn detect_video(file: &String) -> Result<Option<&'static str>, io::Error> {
+    let header: Vec<u8> = read_header(file)?;
+    if header.starts_with(&[0x1A, 0x45, 0xDF, 0xA3]) {
+        // WebM only allows VP8, VP9 and AV1, so those are left alone
+        let is_webm: bool = Path::new(file)
+            .extension()
+            .is_some_and(|e| e.to_string_lossy().to_lowercase() == "webm");
+        return Ok((!is_webm).then_some("matroska"));
+    }
+    match get_ftyp_brand(&header) {
+        Some(b"qt  ") => Ok(Some("mov")),
+        Some(brand) if MP4_BRANDS.iter().any(|b| &b[..] == brand) => Ok(Some("mp4")),
+        _ => Ok(None),
+    }
+}
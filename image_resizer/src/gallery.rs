@@ -0,0 +1,255 @@
+use rust_cli::ansi::Color;
+use rust_cli::ansi::Font;
+
+use chrono::NaiveDateTime;
+use image::DynamicImage;
+use std::collections::BTreeSet;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+use crate::file_type;
+use crate::file_type::FileType;
+use crate::helper;
+use crate::image_file;
+use crate::metadata::Metadata;
+use crate::options::{Options, OutputFormat};
+use crate::pool;
+
+const THUMB_SIZE: u32 = 320;
+const THUMB_QUALITY: u8 = 80;
+// hidden and reserved, so it never meets a library folder of the same name
+pub const THUMBS_DIR: &str = ".gallery_thumbs";
+
+// browsers show these as they are, everything else gets a full size JPEG
+const WEB_TYPES: [FileType; 6] = [
+    FileType::Jpeg,
+    FileType::Png,
+    FileType::Gif,
+    FileType::Bmp,
+    FileType::Webp,
+    FileType::Avif,
+];
+
+const STYLE: &str =
+    "body { font-family: sans-serif; margin: 2em; background: #1e1e1e; color: #ddd; }
+a { color: #8cf; }
+ul { list-style: none; padding: 0; }
+li { margin: 0.3em 0; }
+.grid { display: flex; flex-wrap: wrap; gap: 1em; }
+figure { margin: 0; width: 320px; text-align: center; }
+figure img { max-width: 320px; max-height: 320px; }
+figcaption { font-size: 0.9em; margin-top: 0.3em; }";
+
+struct Photo {
+    folder: String,
+    name: String,
+    full_name: String,
+    date: Option<NaiveDateTime>,
+    thumb_width: u32,
+    thumb_height: u32,
+}
+
+fn get_folder(relative_path: &str) -> String {
+    match relative_path.rfind('/') {
+        Some(idx) => relative_path[..idx].to_string(),
+        None => String::new(),
+    }
+}
+
+fn is_newer(file: &Path, than: &String) -> bool {
+    let modified = |f: &Path| fs::metadata(f).and_then(|m| m.modified()).ok();
+    match (modified(file), modified(Path::new(than))) {
+        (Some(file), Some(than)) => file >= than,
+        _ => false,
+    }
+}
+
+fn export_photo(
+    options: &Options,
+    output_dir: &String,
+    file_path: &String,
+) -> Result<Option<Photo>, io::Error> {
+    let file_type: Option<FileType> = file_type::detect(file_path)?;
+    if file_type.is_none() {
+        return Ok(None);
+    }
+    let file_type: FileType = file_type.unwrap();
+
+    let relative_path: &str = &file_path[options.root_dir.len()..];
+    let folder: String = get_folder(relative_path);
+    let name: String = relative_path[folder.len()..]
+        .trim_start_matches('/')
+        .to_string();
+    // a copy would share the location, so those photos are encoded again without it
+    let copy: bool = WEB_TYPES.contains(&file_type)
+        && !(options.strip_gps && Metadata::read_file(file_path).has_gps());
+    let full_name: String = if copy {
+        name.to_string()
+    } else {
+        format!("{}.jpeg", name)
+    };
+    let folder_dir: &Path = &Path::new(output_dir).join(&folder);
+    let full_file: &Path = &folder_dir.join(&full_name);
+    let thumb_file: &Path = &folder_dir.join(THUMBS_DIR).join(format!("{}.jpeg", name));
+    fs::create_dir_all(folder_dir.join(THUMBS_DIR))?;
+
+    // photos that have not changed since the last export are left alone
+    if is_newer(full_file, file_path) && is_newer(thumb_file, file_path) {
+        let (thumb_width, thumb_height): (u32, u32) =
+            image::image_dimensions(thumb_file).map_err(io::Error::other)?;
+        return Ok(Some(Photo {
+            folder,
+            name,
+            full_name,
+            date: Metadata::read_file(file_path).get_date_taken(),
+            thumb_width,
+            thumb_height,
+        }));
+    }
+
+    let (image, metadata): (DynamicImage, Metadata) = image_file::decode(file_path, file_type)?;
+    if copy {
+        fs::copy(file_path, full_file)?;
+    } else {
+        let data: Vec<u8> = image_file::encode(
+            &image,
+            OutputFormat::Jpeg,
+            options.quality,
+            metadata.to_exif(image.width(), image.height(), options.strip_gps),
+        )?;
+        fs::write(full_file, data)?;
+    }
+
+    // thumbnails never carry metadata
+    let thumb: DynamicImage = image.thumbnail(THUMB_SIZE, THUMB_SIZE);
+    fs::write(
+        thumb_file,
+        image_file::encode(&thumb, OutputFormat::Jpeg, THUMB_QUALITY, None)?,
+    )?;
+
+    Ok(Some(Photo {
+        folder,
+        name,
+        full_name,
+        date: metadata.get_date_taken(),
+        thumb_width: thumb.width(),
+        thumb_height: thumb.height(),
+    }))
+}
+
+fn get_index(title: &str, is_root: bool, subfolders: &[&String], photos: &[&Photo]) -> String {
+    let mut html: String = String::new();
+    html.push_str("<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n");
+    html.push_str("<meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">\n");
+    html.push_str(&format!("<title>{}</title>\n", helper::escape_html(title)));
+    html.push_str(&format!("<style>\n{}\n</style>\n</head>\n<body>\n", STYLE));
+    html.push_str(&format!("<h1>{}</h1>\n", helper::escape_html(title)));
+    if !is_root {
+        html.push_str("<p><a href=\"../index.html\">Up</a></p>\n");
+    }
+
+    if !subfolders.is_empty() {
+        html.push_str("<ul>\n");
+        for subfolder in subfolders {
+            let name: &str = subfolder.rsplit('/').next().unwrap_or(subfolder);
+            html.push_str(&format!(
+                "<li><a href=\"{}/index.html\">{}</a></li>\n",
+                helper::encode_path(name),
+                helper::escape_html(name)
+            ));
+        }
+        html.push_str("</ul>\n");
+    }
+
+    if !photos.is_empty() {
+        html.push_str("<div class=\"grid\">\n");
+        for photo in photos {
+            let caption: String = match photo.date {
+                Some(date) => date.format("%Y-%m-%d %H:%M").to_string(),
+                None => photo.name.to_string(),
+            };
+            html.push_str(&format!(
+                "<figure><a href=\"{}\"><img src=\"{}/{}.jpeg\" width=\"{}\" height=\"{}\" loading=\"lazy\" alt=\"{}\"></a><figcaption>{}</figcaption></figure>\n",
+                helper::encode_path(&photo.full_name),
+                THUMBS_DIR,
+                helper::encode_path(&photo.name),
+                photo.thumb_width,
+                photo.thumb_height,
+                helper::escape_html(&photo.name),
+                helper::escape_html(&caption)
+            ));
+        }
+        html.push_str("</div>\n");
+    }
+    html.push_str("</body>\n</html>\n");
+    html
+}
+
+pub fn run(options: &Options, files: &[String]) -> Result<(), io::Error> {
+    let output_dir: &String = options
+        .output_dir
+        .as_ref()
+        .ok_or(io::Error::other("gallery needs --output <DIR>"))?;
+
+    let photos: Vec<Option<Photo>> = pool::run(files, options.threads, |file_path, log| {
+        let photo: Result<Option<Photo>, io::Error> = export_photo(options, output_dir, file_path);
+        match photo {
+            Ok(photo) => photo,
+            Err(err) => {
+                log.push(format!(
+                    "{}Failed{} to export {}: {}",
+                    Color::Red.as_str(),
+                    Font::Reset.as_str(),
+                    file_path,
+                    err
+                ));
+                None
+            }
+        }
+    });
+    let mut photos: Vec<Photo> = photos.into_iter().flatten().collect();
+    photos.sort_by(|a, b| a.date.cmp(&b.date).then(a.name.cmp(&b.name)));
+
+    // every folder on the way to a photo gets an index
+    let mut folders: BTreeSet<String> = BTreeSet::new();
+    for photo in &photos {
+        let mut folder: String = photo.folder.to_string();
+        while folders.insert(folder.to_string()) && !folder.is_empty() {
+            folder = get_folder(&folder);
+        }
+    }
+
+    let root_name: String = Path::new(&options.root_dir)
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    for folder in &folders {
+        let subfolders: Vec<&String> = folders
+            .iter()
+            .filter(|f| !f.is_empty() && &get_folder(f) == folder)
+            .collect();
+        let folder_photos: Vec<&Photo> = photos.iter().filter(|p| &p.folder == folder).collect();
+        let title: &str = if folder.is_empty() {
+            &root_name
+        } else {
+            folder
+        };
+        let index_dir: &Path = &Path::new(output_dir).join(folder);
+        fs::create_dir_all(index_dir)?;
+        fs::write(
+            index_dir.join("index.html"),
+            get_index(title, folder.is_empty(), &subfolders, &folder_photos),
+        )?;
+    }
+
+    println!(
+        "Exported {}{}{} photos in {} folders to {}index.html",
+        Color::Cyan.as_str(),
+        photos.len(),
+        Font::Reset.as_str(),
+        folders.len(),
+        output_dir
+    );
+    Ok(())
+}
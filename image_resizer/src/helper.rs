@@ -0,0 +1,70 @@
+pub fn get_size_string(bytes: u64) -> String {
+    if bytes > 1024 * 1024 * 1024 {
+        format!("{:.1} GB", bytes as f64 / (1024 * 1024 * 1024) as f64)
+    } else if bytes > 1024 * 1024 {
+        format!("{:.1} MB", bytes as f64 / (1024 * 1024) as f64)
+    } else if bytes > 1024 {
+        format!("{:.1} KB", bytes as f64 / 1024_f64)
+    } else {
+        format!("{} B", bytes)
+    }
+}
+
+pub fn parse_size(size: &str) -> Option<u64> {
+    let size: String = size.trim().to_uppercase();
+    let size: &str = size.trim_end_matches("IB").trim_end_matches('B');
+    let (number, unit): (&str, u64) = match size.chars().last() {
+        Some('K') => (&size[..size.len() - 1], 1024),
+        Some('M') => (&size[..size.len() - 1], 1024 * 1024),
+        Some('G') => (&size[..size.len() - 1], 1024 * 1024 * 1024),
+        _ => (size, 1),
+    };
+    let number: f64 = number.trim().parse::<f64>().ok()?;
+    if number <= 0.0 {
+        return None;
+    }
+    Some((number * unit as f64) as u64)
+}
+
+// bitrates count in thousands, not in KiB like file sizes
+pub fn parse_bitrate(bitrate: &str) -> Option<u64> {
+    let bitrate: String = bitrate.trim().to_uppercase();
+    let bitrate: &str = bitrate.trim_end_matches("BIT/S").trim_end_matches("BPS");
+    let (number, unit): (&str, u64) = match bitrate.chars().last() {
+        Some('K') => (&bitrate[..bitrate.len() - 1], 1000),
+        Some('M') => (&bitrate[..bitrate.len() - 1], 1000 * 1000),
+        _ => (bitrate, 1),
+    };
+    let number: f64 = number.trim().parse::<f64>().ok()?;
+    if number <= 0.0 {
+        return None;
+    }
+    Some((number * unit as f64) as u64)
+}
+
+pub fn get_bitrate_string(bitrate: u64) -> String {
+    if bitrate >= 1000 * 1000 {
+        format!("{:.1} Mbit/s", bitrate as f64 / (1000 * 1000) as f64)
+    } else {
+        format!("{} kbit/s", bitrate / 1000)
+    }
+}
+
+pub fn encode_path(path: &str) -> String {
+    let mut encoded: String = String::new();
+    for byte in path.bytes() {
+        if byte.is_ascii_alphanumeric() || b"/-_.~".contains(&byte) {
+            encoded.push(byte as char);
+        } else {
+            encoded.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    encoded
+}
+
+pub fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
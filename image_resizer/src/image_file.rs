@@ -0,0 +1,324 @@
+use image::codecs::avif::AvifEncoder;
+use image::codecs::jpeg::JpegEncoder;
+use image::codecs::png::PngEncoder;
+use image::imageops::FilterType;
+use image::metadata::Orientation;
+use image::{DynamicImage, ImageDecoder, ImageEncoder, ImageFormat, ImageReader};
+use std::env;
+use std::fs;
+use std::fs::{DirBuilder, File};
+use std::io;
+use std::io::{BufReader, Write};
+use std::os::unix::fs::DirBuilderExt;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::process::{Command, Output, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Mutex, MutexGuard};
+
+use crate::file_type::FileType;
+use crate::helper;
+use crate::metadata::Metadata;
+use crate::options::OutputFormat;
+use crate::trash::Trash;
+
+const AVIF_SPEED: u8 = 6;
+const MIN_TARGET_QUALITY: u8 = 40;
+const MIN_TARGET_DIMENSION: u32 = 64;
+
+static TEMP_COUNT: AtomicUsize = AtomicUsize::new(0);
+static TEMP_DIR: Mutex<Option<PathBuf>> = Mutex::new(None);
+
+fn create_temp_dir() -> Result<PathBuf, io::Error> {
+    let mut count: usize = 0;
+    loop {
+        let temp_dir: PathBuf =
+            env::temp_dir().join(format!("image_resizer_{}_{}", process::id(), count));
+        // anything already there, a planted symlink included, makes this fail
+        match DirBuilder::new().mode(0o700).create(&temp_dir) {
+            Ok(_) => return Ok(temp_dir),
+            Err(err) if err.kind() == io::ErrorKind::AlreadyExists => count += 1,
+            Err(err) => return Err(err),
+        }
+    }
+}
+
+// external decoders write into a directory that only this run can use
+fn get_temp_file() -> Result<PathBuf, io::Error> {
+    let mut temp_dir: MutexGuard<Option<PathBuf>> =
+        TEMP_DIR.lock().unwrap_or_else(|e| e.into_inner());
+    if temp_dir.is_none() {
+        *temp_dir = Some(create_temp_dir()?);
+    }
+    Ok(temp_dir.as_ref().unwrap().join(format!(
+        "{}.png",
+        TEMP_COUNT.fetch_add(1, Ordering::Relaxed)
+    )))
+}
+
+pub fn remove_temp_dir() {
+    let temp_dir: Option<PathBuf> = TEMP_DIR.lock().unwrap_or_else(|e| e.into_inner()).take();
+    if let Some(temp_dir) = temp_dir {
+        let _ = fs::remove_dir_all(temp_dir);
+    }
+}
+
+fn run_decoder(
+    command: &mut Command,
+    program: &str,
+    file_type: FileType,
+) -> Result<Vec<u8>, io::Error> {
+    let output: Output = command.stderr(Stdio::null()).output().map_err(|err| {
+        if err.kind() == io::ErrorKind::NotFound {
+            io::Error::other(format!(
+                "{} images need {} to be installed",
+                file_type.as_str(),
+                program
+            ))
+        } else {
+            err
+        }
+    })?;
+    if !output.status.success() {
+        return Err(io::Error::other(format!(
+            "{} could not decode this {} image",
+            program,
+            file_type.as_str()
+        )));
+    }
+    Ok(output.stdout)
+}
+
+fn decode_external(file: &String, file_type: FileType) -> Result<DynamicImage, io::Error> {
+    if file_type == FileType::Raw {
+        let tiff: Vec<u8> = run_decoder(
+            Command::new("dcraw")
+                .arg("-c")
+                .arg("-w")
+                .arg("-T")
+                .arg(file),
+            "dcraw",
+            file_type,
+        )?;
+        return image::load_from_memory_with_format(&tiff, ImageFormat::Tiff)
+            .map_err(io::Error::other);
+    }
+
+    let program: &str = match file_type {
+        FileType::Heic => "heif-convert",
+        _ => "avifdec",
+    };
+    let temp_file: PathBuf = get_temp_file()?;
+    let result: Result<DynamicImage, io::Error> = run_decoder(
+        Command::new(program).arg(file).arg(&temp_file),
+        program,
+        file_type,
+    )
+    .and_then(|_| image::open(&temp_file).map_err(io::Error::other));
+    let _ = fs::remove_file(&temp_file);
+    result
+}
+
+pub fn decode(file: &String, file_type: FileType) -> Result<(DynamicImage, Metadata), io::Error> {
+    let format: Option<ImageFormat> = file_type.image_format();
+    if format.is_none() {
+        // external decoders already apply the orientation to the pixels
+        let image: DynamicImage = decode_external(file, file_type)?;
+        return Ok((image, Metadata::read_file(file)));
+    }
+
+    let mut reader: ImageReader<BufReader<File>> = ImageReader::open(file)?;
+    reader.set_format(format.unwrap());
+    let mut decoder = reader.into_decoder().map_err(io::Error::other)?;
+    let orientation: Orientation = decoder.orientation().unwrap_or(Orientation::NoTransforms);
+    let metadata: Metadata = Metadata::read(decoder.exif_metadata().unwrap_or(None));
+
+    let mut image: DynamicImage = DynamicImage::from_decoder(decoder).map_err(io::Error::other)?;
+    image.apply_orientation(orientation);
+    Ok((image, metadata))
+}
+
+pub fn read_dimensions(file: &String, file_type: FileType) -> Option<(u32, u32)> {
+    let mut reader: ImageReader<BufReader<File>> = ImageReader::open(file).ok()?;
+    reader.set_format(file_type.image_format()?);
+    let mut decoder = reader.into_decoder().ok()?;
+    let (width, height): (u32, u32) = decoder.dimensions();
+    match decoder.orientation().unwrap_or(Orientation::NoTransforms) {
+        Orientation::Rotate90
+        | Orientation::Rotate270
+        | Orientation::Rotate90FlipH
+        | Orientation::Rotate270FlipH => Some((height, width)),
+        _ => Some((width, height)),
+    }
+}
+
+pub fn resize(image: &DynamicImage, max_size: u32) -> Option<DynamicImage> {
+    if image.width() <= max_size && image.height() <= max_size {
+        return None;
+    }
+    Some(image.resize(max_size, max_size, FilterType::Lanczos3))
+}
+
+fn to_8_bit(image: &DynamicImage) -> DynamicImage {
+    if image.color().has_alpha() {
+        DynamicImage::ImageRgba8(image.to_rgba8())
+    } else {
+        DynamicImage::ImageRgb8(image.to_rgb8())
+    }
+}
+
+fn get_riff_chunk(name: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut chunk: Vec<u8> = name.to_vec();
+    chunk.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    chunk.extend_from_slice(data);
+    if data.len() % 2 == 1 {
+        chunk.push(0);
+    }
+    chunk
+}
+
+fn add_webp_exif(data: Vec<u8>, width: u32, height: u32, exif: &[u8]) -> Vec<u8> {
+    // libwebp writes the simple format unless there is alpha, EXIF needs the extended one
+    let mut chunks: Vec<u8> = data[12..].to_vec();
+    if &chunks[0..4] == b"VP8X" {
+        chunks[8] |= 0x08;
+    } else {
+        let mut header: Vec<u8> = vec![0x08, 0, 0, 0];
+        header.extend_from_slice(&(width - 1).to_le_bytes()[..3]);
+        header.extend_from_slice(&(height - 1).to_le_bytes()[..3]);
+        let mut extended: Vec<u8> = get_riff_chunk(b"VP8X", &header);
+        extended.extend_from_slice(&chunks);
+        chunks = extended;
+    }
+    chunks.extend_from_slice(&get_riff_chunk(b"EXIF", exif));
+
+    let mut webp: Vec<u8> = b"RIFF".to_vec();
+    webp.extend_from_slice(&(chunks.len() as u32 + 4).to_le_bytes());
+    webp.extend_from_slice(b"WEBP");
+    webp.extend_from_slice(&chunks);
+    webp
+}
+
+pub fn encode(
+    image: &DynamicImage,
+    format: OutputFormat,
+    quality: u8,
+    exif: Option<Vec<u8>>,
+) -> Result<Vec<u8>, io::Error> {
+    let mut data: Vec<u8> = vec![];
+    let result: image::ImageResult<()> = match format {
+        OutputFormat::Jpeg => {
+            let mut encoder: JpegEncoder<&mut Vec<u8>> =
+                JpegEncoder::new_with_quality(&mut data, quality);
+            if let Some(exif) = exif {
+                let _ = encoder.set_exif_metadata(exif);
+            }
+            image.to_rgb8().write_with_encoder(encoder)
+        }
+        OutputFormat::Png => {
+            let mut encoder: PngEncoder<&mut Vec<u8>> = PngEncoder::new(&mut data);
+            if let Some(exif) = exif {
+                let _ = encoder.set_exif_metadata(exif);
+            }
+            image.write_with_encoder(encoder)
+        }
+        OutputFormat::Webp => {
+            let image: DynamicImage = to_8_bit(image);
+            match webp::Encoder::from_image(&image) {
+                Ok(encoder) => {
+                    let webp: Vec<u8> = encoder.encode(quality as f32).to_vec();
+                    data = match exif {
+                        Some(exif) => add_webp_exif(webp, image.width(), image.height(), &exif),
+                        None => webp,
+                    };
+                    Ok(())
+                }
+                Err(err) => Err(image::ImageError::IoError(io::Error::other(err))),
+            }
+        }
+        OutputFormat::Avif => {
+            let mut encoder: AvifEncoder<&mut Vec<u8>> =
+                AvifEncoder::new_with_speed_quality(&mut data, AVIF_SPEED, quality);
+            if let Some(exif) = exif {
+                let _ = encoder.set_exif_metadata(exif);
+            }
+            to_8_bit(image).write_with_encoder(encoder)
+        }
+    };
+    result.map_err(io::Error::other)?;
+    Ok(data)
+}
+
+// highest quality that fits, then smaller dimensions once quality alone is not enough
+pub fn encode_to_size(
+    image: &DynamicImage,
+    format: OutputFormat,
+    max_quality: u8,
+    metadata: &Metadata,
+    strip_gps: bool,
+    target_size: Option<u64>,
+) -> Result<(Vec<u8>, u8, Option<DynamicImage>), io::Error> {
+    let mut scaled: Option<DynamicImage> = None;
+    loop {
+        let current: &DynamicImage = scaled.as_ref().unwrap_or(image);
+        let exif = || metadata.to_exif(current.width(), current.height(), strip_gps);
+
+        let data: Vec<u8> = encode(current, format, max_quality, exif())?;
+        if target_size.is_none_or(|t| data.len() as u64 <= t) {
+            return Ok((data, max_quality, scaled));
+        }
+        let target_size: u64 = target_size.unwrap();
+        let mut smallest: usize = data.len();
+
+        if format != OutputFormat::Png {
+            let mut best: Option<(Vec<u8>, u8)> = None;
+            let mut low: u8 = MIN_TARGET_QUALITY.min(max_quality);
+            let mut high: u8 = max_quality - 1;
+            while low <= high {
+                let quality: u8 = low + (high - low) / 2;
+                let data: Vec<u8> = encode(current, format, quality, exif())?;
+                if data.len() as u64 <= target_size {
+                    best = Some((data, quality));
+                    low = quality + 1;
+                } else {
+                    smallest = smallest.min(data.len());
+                    if quality == 0 {
+                        break;
+                    }
+                    high = quality - 1;
+                }
+            }
+            if let Some((data, quality)) = best {
+                return Ok((data, quality, scaled));
+            }
+        }
+
+        let scale: f64 = ((target_size as f64 / smallest as f64).sqrt() * 0.95).min(0.9);
+        let width: u32 = (current.width() as f64 * scale) as u32;
+        let height: u32 = (current.height() as f64 * scale) as u32;
+        if width < MIN_TARGET_DIMENSION || height < MIN_TARGET_DIMENSION {
+            return Err(io::Error::other(format!(
+                "could not fit under {}",
+                helper::get_size_string(target_size)
+            )));
+        }
+        scaled = Some(current.resize(width, height, FilterType::Lanczos3));
+    }
+}
+
+pub fn save(data: &[u8], file: &String, trash: Option<&Trash>) -> Result<(), io::Error> {
+    let temp_file: String = format!("{}.tmp", file);
+    let mut temp: File = File::create(&temp_file)?;
+    if let Err(err) = temp.write_all(data).and_then(|_| temp.sync_all()) {
+        drop(temp);
+        let _ = fs::remove_file(&temp_file);
+        return Err(err);
+    }
+
+    if let Some(trash) = trash {
+        if Path::new(file).exists() {
+            trash.put(file)?;
+        }
+    }
+    fs::rename(&temp_file, file)
+}
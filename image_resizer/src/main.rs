@@ -1,18 +1,41 @@
 extern crate rust_cli;
 
+mod change;
+mod dupes;
+mod file_type;
+mod gallery;
+mod helper;
+mod image_file;
+mod metadata;
+mod options;
+mod plan;
+mod pool;
+mod preview;
+mod progress;
+mod rules;
+mod sort;
+mod state;
+mod trash;
+mod video;
+
 use rust_cli::ansi::Color;
 use rust_cli::ansi::Font;
-use rust_cli::commands::Operation;
 use rust_cli::prompts::select::Select;
 
+use image::DynamicImage;
 use std::env;
 use std::env::VarError;
 use std::fs;
 use std::fs::{DirEntry, ReadDir};
 use std::io;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
-const MAX_SIZE: u32 = 2400;
+use crate::change::{Change, Encoded, Failure};
+use crate::options::{Mode, Options};
+use crate::plan::Job;
+use crate::rules::Rules;
+use crate::state::{FileState, State};
+use crate::trash::Trash;
 
 fn get_recursive_folders_files(orig_path: &String, path: &String) -> (Vec<String>, Vec<String>) {
     if path.contains(".git") {
@@ -48,6 +71,10 @@ fn get_recursive_folders_files(orig_path: &String, path: &String) -> (Vec<String
         if entry_path_buff.is_file() {
             files.push(entry_path.to_string());
         } else if entry_path_buff.is_dir() {
+            // a gallery exported into the library is not part of it
+            if entry.file_name() == gallery::THUMBS_DIR {
+                continue;
+            }
             let sub_folders_files: (Vec<String>, Vec<String>) =
                 get_recursive_folders_files(orig_path, &entry_path.to_string());
             for folder in sub_folders_files.0 {
@@ -61,137 +88,365 @@ fn get_recursive_folders_files(orig_path: &String, path: &String) -> (Vec<String
     (folders, files)
 }
 
-fn get_image_dim(file: &String, dim: &str) -> Result<u32, io::Error> {
-    Ok(
-        Operation::new(format!("identify -format %[{}] {}", dim, file))
-            .output()?
-            .parse::<u32>()
-            .unwrap_or(0),
-    )
+fn get_folder_files(options: &Options, files: &[String]) -> Vec<String> {
+    files
+        .iter()
+        .filter(|f| {
+            options.folders.is_empty()
+                || options
+                    .folders
+                    .iter()
+                    .any(|d| f.starts_with(&format!("{}{}/", &options.root_dir, d)))
+        })
+        .cloned()
+        .collect()
+}
+
+fn copy_file(old_file: &String, new_file: &String) -> Result<(), io::Error> {
+    if let Some(parent) = Path::new(new_file).parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::copy(old_file, new_file)?;
+    Ok(())
+}
+
+fn get_result(
+    needs_convert: bool,
+    resized: Option<&DynamicImage>,
+    strip_gps: bool,
+    fitted: Option<(u8, usize)>,
+) -> String {
+    let mut changes: Vec<String> = vec![];
+    if needs_convert {
+        changes.push(String::from("converted"));
+    }
+    if let Some(image) = resized {
+        changes.push(format!("resized to {}x{}", image.width(), image.height()));
+    }
+    if strip_gps {
+        changes.push(String::from("gps removed"));
+    }
+    if let Some((quality, size)) = fitted {
+        changes.push(format!(
+            "quality {} at {}",
+            quality,
+            helper::get_size_string(size as u64)
+        ));
+    }
+    if changes.is_empty() {
+        return String::from("unchanged");
+    }
+    changes.join(", ")
 }
 
-fn convert_file(old_file: &String, new_file: &String) -> Result<bool, io::Error> {
-    Ok(
-        Operation::new(format!("convert {} {} ", old_file, new_file))
-            .run()?
-            .success(),
-    )
+fn process_file(
+    job: &Job,
+    options: &Options,
+    trash: Option<&Trash>,
+    log: &mut Vec<String>,
+) -> Result<Option<String>, io::Error> {
+    let file_path: &String = &job.file_path;
+    let file_name: &String = &job.file_name;
+    let needs_convert: bool = job.needs_convert;
+    let change: Result<Change, Failure> = change::get_change(job, options);
+    if change.as_ref().is_ok_and(|c| !c.decoded) {
+        if options.output_dir.is_some() {
+            copy_file(file_path, file_name)?;
+        }
+        return Ok(Some(get_result(false, None, false, None)));
+    }
+
+    log.push(format!(
+        "Processing {}{}{}...",
+        Color::Cyan.as_str(),
+        file_path,
+        Font::Reset.as_str()
+    ));
+    let change: Change = match change {
+        Ok(change) => change,
+        Err(failure) => {
+            let (step, err): (&str, io::Error) = match failure {
+                Failure::Decode(err) => ("Decode", err),
+                Failure::Encode(err) => ("Encode", err),
+            };
+            log.push(format!(
+                "    {}Failed to {}{} ({})",
+                Color::Red.as_str(),
+                step,
+                Font::Reset.as_str(),
+                err
+            ));
+            return Ok(None);
+        }
+    };
+
+    if let (Some(_), Some((width, height))) = (job.max_size, change.dimensions) {
+        if !change.resized {
+            log.push(format!(
+                "    Image is {}small enough{}",
+                Color::Green.as_str(),
+                Font::Reset.as_str()
+            ));
+        } else if height > width {
+            log.push(format!(
+                "    Image is {}too tall{} (height: {})",
+                Color::Red.as_str(),
+                Font::Reset.as_str(),
+                height
+            ));
+        } else {
+            log.push(format!(
+                "    Image is {}too wide{} (width: {})",
+                Color::Red.as_str(),
+                Font::Reset.as_str(),
+                width
+            ));
+        }
+    }
+    if change.strip_gps {
+        log.push(format!(
+            "    {}Removing GPS location{}",
+            Color::Yellow.as_str(),
+            Font::Reset.as_str()
+        ));
+    }
+
+    let encoded: Encoded = match change.encoded {
+        Some(encoded) => encoded,
+        None => {
+            if options.output_dir.is_some() {
+                copy_file(file_path, file_name)?;
+            }
+            return Ok(Some(get_result(false, None, false, None)));
+        }
+    };
+    if let Some(target_size) = options.target_size {
+        log.push(format!(
+            "    Fits under {} at {}quality {}{} ({}, {}x{})",
+            helper::get_size_string(target_size),
+            Color::Cyan.as_str(),
+            encoded.quality,
+            Font::Reset.as_str(),
+            helper::get_size_string(encoded.data.len() as u64),
+            encoded.image.width(),
+            encoded.image.height()
+        ));
+    }
+
+    if let Some(parent) = Path::new(file_name).parent() {
+        fs::create_dir_all(parent)?;
+    }
+    if let Err(err) = image_file::save(&encoded.data, file_name, trash) {
+        log.push(format!(
+            "    {}Failed to Save{} ({})",
+            Color::Red.as_str(),
+            Font::Reset.as_str(),
+            err
+        ));
+        return Ok(None);
+    }
+    if options.output_dir.is_some() {
+        log.push(format!(
+            "    {}Saved{} to {}",
+            Color::Green.as_str(),
+            Font::Reset.as_str(),
+            &file_name
+        ));
+    } else if needs_convert {
+        log.push(format!(
+            "    {}Convert Successful{}",
+            Color::Green.as_str(),
+            Font::Reset.as_str()
+        ));
+        match trash {
+            Some(trash) => trash.put(file_path)?,
+            None => fs::remove_file(file_path)?,
+        }
+    }
+    Ok(Some(get_result(
+        needs_convert,
+        (change.resized || encoded.scaled).then_some(&encoded.image),
+        change.strip_gps,
+        options
+            .target_size
+            .map(|_| (encoded.quality, encoded.data.len())),
+    )))
 }
 
 fn main() -> Result<(), io::Error> {
+    let result: Result<(), io::Error> = run();
+    image_file::remove_temp_dir();
+    result
+}
+
+fn run() -> Result<(), io::Error> {
     let home_dir: Result<String, VarError> = env::var("HOME");
     if home_dir.is_err() {
         return Err(io::Error::other("HOME directory could not be determined"));
     }
     let home_dir: String = home_dir.unwrap();
-    let pictures_dir: String = format!("{}/Pictures/", &home_dir);
+
+    let args: Vec<String> = env::args().collect();
+    let options: Option<Options> = Options::parse(&home_dir, &args)?;
+    if options.is_none() {
+        return Ok(());
+    }
+    let options: Options = options.unwrap();
+    if !Path::new(&options.root_dir).is_dir() {
+        return Err(io::Error::other(format!(
+            "{} is not a directory",
+            &options.root_dir
+        )));
+    }
 
     let folders_files: (Vec<String>, Vec<String>) =
-        get_recursive_folders_files(&pictures_dir, &pictures_dir);
+        get_recursive_folders_files(&options.root_dir, &options.root_dir);
 
-    let make_small_folders: Vec<String> = Select::new()
-        .title("Choose folders to make small")
-        .options(&folders_files.0)
-        .run_multi_select()?
-        .iter()
-        .map(|t| t.1.to_string())
-        .collect();
+    for folder in &options.folders {
+        if !folders_files.0.contains(folder) {
+            return Err(io::Error::other(format!(
+                "{} is not a folder in {}",
+                folder, &options.root_dir
+            )));
+        }
+    }
 
-    for file_path in &folders_files.1 {
-        let extension: Option<(&str, &str)> = file_path.rsplit_once(".");
-        if extension.is_none() {
-            continue;
+    let trash: Option<Trash> = if options.trash {
+        Some(Trash::new(&home_dir)?)
+    } else {
+        None
+    };
+
+    match options.mode {
+        Mode::Dupes => {
+            let files: Vec<String> = get_folder_files(&options, &folders_files.1);
+            return dupes::run(&options, &files, trash.as_ref());
         }
-        let extension: (&str, &str) = extension.unwrap();
-        let file_name_no_extension: &str = extension.0;
-        let extension: &str = extension.1;
-        let file_name: String = format!("{}.jpeg", file_name_no_extension);
-        if extension != "jpeg" {
-            println!(
-                "Attempting to convert {}{}{} to a jpeg...",
-                Color::Cyan.as_str(),
-                file_path,
-                Font::Reset.as_str()
-            );
-            if convert_file(&file_path, &file_name).is_ok_and(|x| x) {
-                println!(
-                    "    {}Convert Successful{}",
-                    Color::Green.as_str(),
-                    Font::Reset.as_str()
-                );
-                Operation::new(format!("rm -f {}", &file_path)).run()?;
-            } else {
-                println!(
-                    "    {}Failed to Convert{}",
-                    Color::Red.as_str(),
-                    Font::Reset.as_str()
-                );
-                continue;
-            }
+        Mode::Sort => {
+            let files: Vec<String> = get_folder_files(&options, &folders_files.1);
+            return sort::run(&options, &folders_files.1, &files);
+        }
+        Mode::Gallery => {
+            let files: Vec<String> = get_folder_files(&options, &folders_files.1);
+            return gallery::run(&options, &files);
         }
+        Mode::Video => {
+            let files: Vec<String> = get_folder_files(&options, &folders_files.1);
+            return video::run(&options, &files, trash.as_ref());
+        }
+        Mode::Resize => (),
+    }
 
-        for folder in &make_small_folders {
-            if file_name.contains(folder) {
-                println!(
-                    "Checking size of {}{}{}...",
-                    Color::Cyan.as_str(),
-                    file_name,
-                    Font::Reset.as_str()
-                );
+    let rules: Option<Rules> = Rules::read(&options.rules_file)?;
+    let make_small_folders: Vec<String> = if options.folders.is_empty() && rules.is_none() {
+        Select::new()
+            .title("Choose folders to make small")
+            .options(&folders_files.0)
+            .run_multi_select()?
+            .iter()
+            .map(|t| t.1.to_string())
+            .collect()
+    } else {
+        options.folders.clone()
+    };
 
-                let height: u32 = get_image_dim(&file_name, "h")?;
-                let width: u32 = get_image_dim(&file_name, "w")?;
+    let mut state: State = State::read(&options);
+    let files: Vec<String> = folders_files
+        .1
+        .into_iter()
+        .filter(|f| !state.is_state_file(f) && f != &options.rules_file)
+        .collect();
 
-                if height > MAX_SIZE || width > MAX_SIZE {
-                    if height > width {
-                        println!(
-                            "    Image is {}too tall{} (height: {})",
-                            Color::Red.as_str(),
-                            Font::Reset.as_str(),
-                            height
-                        );
-                        Operation::new(format!(
-                            "convert {} -resize x{} {}",
-                            &file_name, MAX_SIZE, &file_name
-                        ))
-                        .run()?;
-                    } else {
-                        println!(
-                            "    Image is {}too wide{} (width: {})",
+    let (jobs, mut skipped): (Vec<Job>, Vec<(String, String)>) =
+        plan::plan_files(&files, &options, &make_small_folders, rules.as_ref());
+    let jobs: Vec<Job> = plan::resolve_collisions(jobs, &mut skipped, &options)?;
+    let (jobs, unchanged): (Vec<Job>, Vec<Job>) = jobs
+        .into_iter()
+        .partition(|j| options.force || !state.is_unchanged(j, &state::get_settings(&options, j)));
+
+    if options.dry_run {
+        preview::run(&options, &jobs);
+    } else {
+        let file_states: Vec<Option<(String, FileState)>> =
+            pool::run(&jobs, options.threads, |job, log| {
+                let result: Option<String> = match process_file(job, &options, trash.as_ref(), log)
+                {
+                    Ok(result) => result,
+                    Err(err) => {
+                        log.push(format!(
+                            "    {}Failed{} {}: {}",
                             Color::Red.as_str(),
                             Font::Reset.as_str(),
-                            width
-                        );
-                        println!(
-                            "    Image is {}too wide{}",
-                            Color::Red.as_str(),
-                            Font::Reset.as_str()
-                        );
-                        Operation::new(format!(
-                            "convert {} -resize {} {}",
-                            &file_name, MAX_SIZE, &file_name
-                        ))
-                        .run()?;
+                            &job.file_path,
+                            err
+                        ));
+                        None
                     }
+                };
+
+                // converted files are remembered by their new name
+                let file: &String = if options.output_dir.is_none() {
+                    &job.file_name
                 } else {
-                    println!(
-                        "    Image is {}small enough{}",
-                        Color::Green.as_str(),
-                        Font::Reset.as_str()
-                    );
-                }
-
-                break;
-            }
+                    &job.file_path
+                };
+                result.and_then(|r| {
+                    FileState::read(file, state::get_settings(&options, job), r)
+                        .ok()
+                        .map(|s| (file.to_string(), s))
+                })
+            });
+        for (file, file_state) in file_states.into_iter().flatten() {
+            state.set(&file, file_state);
+        }
+        if let Err(err) = state.write() {
+            println!(
+                "{}Failed{} to save {}: {}",
+                Color::Red.as_str(),
+                Font::Reset.as_str(),
+                state::STATE_FILE,
+                err
+            );
         }
     }
+    skipped.sort();
 
-    println!(
-        "Finished processing {}{}{} images",
-        Color::Cyan.as_str(),
-        &folders_files.1.len(),
-        Font::Reset.as_str()
-    );
+    if !skipped.is_empty() {
+        println!(
+            "Skipped {}{}{} files:",
+            Color::Yellow.as_str(),
+            skipped.len(),
+            Font::Reset.as_str()
+        );
+        for (file_path, reason) in &skipped {
+            println!("    {} ({})", file_path, reason);
+        }
+    }
+
+    if !unchanged.is_empty() {
+        println!(
+            "Skipped {}{}{} unchanged images (use --force to process them again)",
+            Color::Cyan.as_str(),
+            unchanged.len(),
+            Font::Reset.as_str()
+        );
+    }
+
+    if options.dry_run {
+        println!(
+            "Would process {}{}{} images, nothing was changed",
+            Color::Cyan.as_str(),
+            jobs.len(),
+            Font::Reset.as_str()
+        );
+    } else {
+        println!(
+            "Finished processing {}{}{} images",
+            Color::Cyan.as_str(),
+            jobs.len(),
+            Font::Reset.as_str()
+        );
+    }
 
     Ok(())
 }
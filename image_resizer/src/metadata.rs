@@ -0,0 +1,97 @@
+use chrono::{NaiveDate, NaiveDateTime};
+use exif::experimental::Writer;
+use exif::{Context, DateTime, Exif, Field, In, Reader, Tag, Value};
+use std::fs::File;
+use std::io::{BufReader, Cursor};
+
+pub struct Metadata {
+    exif: Option<Exif>,
+}
+
+impl Metadata {
+    pub fn read(raw: Option<Vec<u8>>) -> Metadata {
+        Metadata {
+            exif: raw.and_then(|r| Reader::new().read_raw(r).ok()),
+        }
+    }
+
+    pub fn read_file(file: &String) -> Metadata {
+        Metadata {
+            exif: File::open(file).ok().and_then(|f| {
+                Reader::new()
+                    .read_from_container(&mut BufReader::new(f))
+                    .ok()
+            }),
+        }
+    }
+
+    pub fn has_gps(&self) -> bool {
+        self.exif
+            .as_ref()
+            .is_some_and(|e| e.fields().any(|f| f.tag.context() == Context::Gps))
+    }
+
+    pub fn get_date_taken(&self) -> Option<NaiveDateTime> {
+        let field: &Field = self
+            .exif
+            .as_ref()?
+            .get_field(Tag::DateTimeOriginal, In::PRIMARY)?;
+        let date_time: DateTime = match &field.value {
+            Value::Ascii(values) => DateTime::from_ascii(values.first()?).ok()?,
+            _ => return None,
+        };
+        NaiveDate::from_ymd_opt(
+            date_time.year as i32,
+            date_time.month as u32,
+            date_time.day as u32,
+        )?
+        .and_hms_opt(
+            date_time.hour as u32,
+            date_time.minute as u32,
+            date_time.second as u32,
+        )
+    }
+
+    pub fn to_exif(&self, width: u32, height: u32, strip_gps: bool) -> Option<Vec<u8>> {
+        let exif: &Exif = self.exif.as_ref()?;
+
+        // pixels are already rotated and resized, so these fields are rewritten
+        let replaced: [Field; 3] = [
+            Field {
+                tag: Tag::Orientation,
+                ifd_num: In::PRIMARY,
+                value: Value::Short(vec![1]),
+            },
+            Field {
+                tag: Tag::PixelXDimension,
+                ifd_num: In::PRIMARY,
+                value: Value::Long(vec![width]),
+            },
+            Field {
+                tag: Tag::PixelYDimension,
+                ifd_num: In::PRIMARY,
+                value: Value::Long(vec![height]),
+            },
+        ];
+
+        let mut writer: Writer = Writer::new();
+        for field in exif.fields() {
+            if field.ifd_num != In::PRIMARY
+                || field.tag == Tag::MakerNote
+                || replaced.iter().any(|r| r.tag == field.tag)
+                || matches!(field.value, Value::Unknown(..))
+                || (strip_gps && field.tag.context() == Context::Gps)
+            {
+                continue;
+            }
+            writer.push_field(field);
+        }
+        for field in &replaced {
+            writer.push_field(field);
+        }
+
+        let mut buffer: Cursor<Vec<u8>> = Cursor::new(vec![]);
+        writer.write(&mut buffer, exif.little_endian()).ok()?;
+        Some(buffer.into_inner())
+    }
+}
@@ -0,0 +1,546 @@
+use rust_cli::ansi::font;
+use rust_cli::ansi::Color;
+
+use chrono::format::{Item, StrftimeItems};
+
+use std::fs;
+use std::io;
+use std::path::{self, Component, PathBuf};
+use std::thread;
+
+use crate::file_type::FileType;
+use crate::helper;
+use crate::rules;
+
+const DEFAULT_MAX_SIZE: u32 = 2400;
+const DEFAULT_QUALITY: u8 = 90;
+const DEFAULT_DISTANCE: u32 = 8;
+const DEFAULT_LAYOUT: &str = "%Y/%m";
+const DEFAULT_VIDEO_SIZE: u32 = 1920;
+const DEFAULT_MAX_BITRATE: u64 = 12_000_000;
+const DEFAULT_CRF: u8 = 26;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Mode {
+    Resize,
+    Dupes,
+    Sort,
+    Gallery,
+    Video,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum OutputFormat {
+    Jpeg,
+    Png,
+    Webp,
+    Avif,
+}
+
+impl OutputFormat {
+    pub fn parse(value: &str) -> Option<OutputFormat> {
+        match value.to_lowercase().as_str() {
+            "jpeg" | "jpg" => Some(OutputFormat::Jpeg),
+            "png" => Some(OutputFormat::Png),
+            "webp" => Some(OutputFormat::Webp),
+            "avif" => Some(OutputFormat::Avif),
+            _ => None,
+        }
+    }
+
+    pub fn file_type(&self) -> FileType {
+        match self {
+            OutputFormat::Jpeg => FileType::Jpeg,
+            OutputFormat::Png => FileType::Png,
+            OutputFormat::Webp => FileType::Webp,
+            OutputFormat::Avif => FileType::Avif,
+        }
+    }
+
+    pub fn has_extension(&self, extension: &str) -> bool {
+        match self {
+            OutputFormat::Jpeg => matches!(extension.to_lowercase().as_str(), "jpeg" | "jpg"),
+            _ => extension.to_lowercase() == self.extension(),
+        }
+    }
+
+    pub fn extension(&self) -> &str {
+        match self {
+            OutputFormat::Jpeg => "jpeg",
+            OutputFormat::Png => "png",
+            OutputFormat::Webp => "webp",
+            OutputFormat::Avif => "avif",
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum VideoCodec {
+    H264,
+    H265,
+    Av1,
+}
+
+impl VideoCodec {
+    fn parse(value: &str) -> Option<VideoCodec> {
+        match value.to_lowercase().as_str() {
+            "h264" | "avc" => Some(VideoCodec::H264),
+            "h265" | "hevc" => Some(VideoCodec::H265),
+            "av1" => Some(VideoCodec::Av1),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        match self {
+            VideoCodec::H264 => "h264",
+            VideoCodec::H265 => "h265",
+            VideoCodec::Av1 => "av1",
+        }
+    }
+
+    pub fn encoder(&self) -> &str {
+        match self {
+            VideoCodec::H264 => "libx264",
+            VideoCodec::H265 => "libx265",
+            VideoCodec::Av1 => "libsvtav1",
+        }
+    }
+
+    fn max_crf(&self) -> u8 {
+        match self {
+            VideoCodec::Av1 => 63,
+            _ => 51,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum CollisionStrategy {
+    Suffix,
+    Skip,
+    Fail,
+}
+
+impl CollisionStrategy {
+    fn parse(value: &str) -> Option<CollisionStrategy> {
+        match value.to_lowercase().as_str() {
+            "suffix" => Some(CollisionStrategy::Suffix),
+            "skip" => Some(CollisionStrategy::Skip),
+            "fail" => Some(CollisionStrategy::Fail),
+            _ => None,
+        }
+    }
+}
+
+pub struct Options {
+    pub mode: Mode,
+    pub root_dir: String,
+    pub max_size: u32,
+    pub format: OutputFormat,
+    pub quality: u8,
+    pub target_size: Option<u64>,
+    pub output_dir: Option<String>,
+    pub rules_file: String,
+    pub trash: bool,
+    pub strip_gps: bool,
+    pub on_collision: CollisionStrategy,
+    pub threads: usize,
+    pub force: bool,
+    pub distance: u32,
+    pub layout: String,
+    pub video_size: u32,
+    pub max_bitrate: u64,
+    pub codec: VideoCodec,
+    pub crf: u8,
+    pub dry_run: bool,
+    pub folders: Vec<String>,
+}
+
+fn print_option(option: &str, description: &str) {
+    font::text_color(Color::Cyan);
+    print!("  {: <26}", option);
+    font::reset();
+    print!("{}", description);
+    println!();
+}
+
+pub fn print_help() {
+    println!();
+    println!("Image Resizer");
+    println!("Command line program that converts and shrinks images in a photo library");
+    println!();
+    print!("Usage: ");
+    font::text_color(Color::Blue);
+    print!("image_resizer [COMMAND] [OPTIONS] [FOLDER]...");
+    font::reset();
+    println!();
+    println!();
+    println!("Commands:");
+    print_option("dupes", "Find duplicate and near-duplicate photos");
+    print_option("sort", "Move images into date folders under the root");
+    print_option(
+        "gallery",
+        "Build a static HTML gallery with thumbnails in --output",
+    );
+    print_option("video", "Re-encode videos that are too large with ffmpeg");
+    println!();
+    println!("Options:");
+    print_option(
+        "[FOLDER]...",
+        "Folders (relative to root) to make small, or to limit other commands to",
+    );
+    print_option(
+        "-r, --root <PATH>",
+        "Root directory of the library ($HOME/Pictures)",
+    );
+    print_option("-s, --max-size <PIXELS>", "Maximum width or height (2400)");
+    print_option(
+        "-f, --format <FORMAT>",
+        "Output format: jpeg, png, webp, avif (jpeg)",
+    );
+    print_option("-q, --quality <1-100>", "Output quality (90)");
+    print_option(
+        "-b, --target-size <SIZE>",
+        "Lower quality, then size, until images fit (e.g. 500K)",
+    );
+    print_option(
+        "-o, --output <DIR>",
+        "Write images (or the gallery) into a mirror of the library in DIR",
+    );
+    print_option(
+        "--rules <FILE>",
+        "Per folder size, quality and format (ROOT/.image_resizer_rules)",
+    );
+    print_option(
+        "-t, --trash",
+        "Move replaced originals, or all but the largest dupe, to the Trash",
+    );
+    print_option("-g, --strip-gps", "Remove GPS location from image metadata");
+    print_option(
+        "-c, --on-collision <MODE>",
+        "When two images get the same name: suffix, skip, fail (suffix)",
+    );
+    print_option(
+        "-j, --threads <COUNT>",
+        "Images processed in parallel (number of CPUs)",
+    );
+    print_option(
+        "--force",
+        "Process images that are unchanged since the last run",
+    );
+    print_option(
+        "-d, --distance <BITS>",
+        "Bits two photos may differ by to count as dupes (8)",
+    );
+    print_option(
+        "-l, --layout <PATTERN>",
+        "Date folders that sort moves images into (%Y/%m)",
+    );
+    print_option(
+        "--video-size <PIXELS>",
+        "Maximum video width or height (1920)",
+    );
+    print_option(
+        "--max-bitrate <RATE>",
+        "Re-encode videos above this bitrate (12M)",
+    );
+    print_option("--codec <CODEC>", "Video codec: h264, h265, av1 (h265)");
+    print_option("--crf <NUMBER>", "Video quality, lower is better (26)");
+    print_option(
+        "-n, --dry-run",
+        "List what would change and the sizes before and after",
+    );
+    print_option("-h, --help", "Print help information");
+    println!();
+}
+
+// like canonicalize, but the end of the path does not have to exist yet
+fn resolve_path(path: &str) -> Result<PathBuf, io::Error> {
+    let mut resolved: PathBuf = PathBuf::new();
+    for component in path::absolute(path)?.components() {
+        match component {
+            Component::CurDir => (),
+            Component::ParentDir => {
+                resolved.pop();
+            }
+            component => {
+                resolved.push(component);
+                if resolved.exists() {
+                    resolved = fs::canonicalize(&resolved)?;
+                }
+            }
+        }
+    }
+    Ok(resolved)
+}
+
+fn get_value(args: &[String], idx: usize) -> Result<&String, io::Error> {
+    args.get(idx + 1)
+        .ok_or(io::Error::other(format!("{} requires a value", args[idx])))
+}
+
+fn get_number<T: std::str::FromStr>(args: &[String], idx: usize) -> Result<T, io::Error> {
+    let value: &String = get_value(args, idx)?;
+    value
+        .parse::<T>()
+        .map_err(|_| io::Error::other(format!("{} is not a valid value for {}", value, args[idx])))
+}
+
+impl Options {
+    pub fn parse(home_dir: &String, args: &[String]) -> Result<Option<Options>, io::Error> {
+        let mut options: Options = Options {
+            mode: Mode::Resize,
+            root_dir: format!("{}/Pictures", home_dir),
+            max_size: DEFAULT_MAX_SIZE,
+            format: OutputFormat::Jpeg,
+            quality: DEFAULT_QUALITY,
+            target_size: None,
+            output_dir: None,
+            rules_file: String::new(),
+            trash: false,
+            strip_gps: false,
+            on_collision: CollisionStrategy::Suffix,
+            threads: thread::available_parallelism()
+                .map(|n| n.get())
+                .unwrap_or(1),
+            force: false,
+            distance: DEFAULT_DISTANCE,
+            layout: String::from(DEFAULT_LAYOUT),
+            video_size: DEFAULT_VIDEO_SIZE,
+            max_bitrate: DEFAULT_MAX_BITRATE,
+            codec: VideoCodec::H265,
+            crf: DEFAULT_CRF,
+            dry_run: false,
+            folders: vec![],
+        };
+
+        let mut idx: usize = 1;
+        match args.get(idx).map(|a| a.as_str()) {
+            Some("dupes") => {
+                options.mode = Mode::Dupes;
+                idx += 1;
+            }
+            Some("sort") => {
+                options.mode = Mode::Sort;
+                idx += 1;
+            }
+            Some("gallery") => {
+                options.mode = Mode::Gallery;
+                idx += 1;
+            }
+            Some("video") => {
+                options.mode = Mode::Video;
+                idx += 1;
+            }
+            _ => (),
+        }
+        while idx < args.len() {
+            match args[idx].as_str() {
+                "-h" | "--help" => {
+                    print_help();
+                    return Ok(None);
+                }
+                "-r" | "--root" => {
+                    options.root_dir = get_value(args, idx)?.to_string();
+                    idx += 1;
+                }
+                "-s" | "--max-size" => {
+                    options.max_size = get_number::<u32>(args, idx)?;
+                    if options.max_size == 0 {
+                        return Err(io::Error::other("max size must be at least 1"));
+                    }
+                    idx += 1;
+                }
+                "-f" | "--format" => {
+                    let value: &String = get_value(args, idx)?;
+                    let format: Option<OutputFormat> = OutputFormat::parse(value);
+                    if format.is_none() {
+                        return Err(io::Error::other(format!(
+                            "{} is not a supported format",
+                            value
+                        )));
+                    }
+                    options.format = format.unwrap();
+                    idx += 1;
+                }
+                "-q" | "--quality" => {
+                    options.quality = get_number::<u8>(args, idx)?;
+                    if options.quality == 0 || options.quality > 100 {
+                        return Err(io::Error::other("quality must be between 1 and 100"));
+                    }
+                    idx += 1;
+                }
+                "-b" | "--target-size" => {
+                    let value: &String = get_value(args, idx)?;
+                    options.target_size = helper::parse_size(value);
+                    if options.target_size.is_none() {
+                        return Err(io::Error::other(format!("{} is not a valid size", value)));
+                    }
+                    idx += 1;
+                }
+                "-o" | "--output" => {
+                    options.output_dir = Some(get_value(args, idx)?.to_string());
+                    idx += 1;
+                }
+                "-c" | "--on-collision" => {
+                    let value: &String = get_value(args, idx)?;
+                    let on_collision: Option<CollisionStrategy> = CollisionStrategy::parse(value);
+                    if on_collision.is_none() {
+                        return Err(io::Error::other(format!(
+                            "{} is not a collision strategy",
+                            value
+                        )));
+                    }
+                    options.on_collision = on_collision.unwrap();
+                    idx += 1;
+                }
+                "-j" | "--threads" => {
+                    options.threads = get_number::<usize>(args, idx)?;
+                    if options.threads == 0 {
+                        return Err(io::Error::other("threads must be at least 1"));
+                    }
+                    idx += 1;
+                }
+                "-d" | "--distance" => {
+                    options.distance = get_number::<u32>(args, idx)?;
+                    if options.distance > 64 {
+                        return Err(io::Error::other("distance must be between 0 and 64"));
+                    }
+                    idx += 1;
+                }
+                "-l" | "--layout" => {
+                    let value: &String = get_value(args, idx)?;
+                    if value.trim_matches('/').is_empty()
+                        || StrftimeItems::new(value).any(|i| i == Item::Error)
+                    {
+                        return Err(io::Error::other(format!("{} is not a valid layout", value)));
+                    }
+                    // sorted photos have to stay inside the root directory
+                    if value.starts_with('/') || value.split('/').any(|c| c == "..") {
+                        return Err(io::Error::other(format!(
+                            "{} has to be a relative layout without ..",
+                            value
+                        )));
+                    }
+                    options.layout = value.trim_matches('/').to_string();
+                    idx += 1;
+                }
+                "--video-size" => {
+                    options.video_size = get_number::<u32>(args, idx)?;
+                    if options.video_size < 2 {
+                        return Err(io::Error::other("video size must be at least 2"));
+                    }
+                    idx += 1;
+                }
+                "--max-bitrate" => {
+                    let value: &String = get_value(args, idx)?;
+                    let max_bitrate: Option<u64> = helper::parse_bitrate(value);
+                    if max_bitrate.is_none() {
+                        return Err(io::Error::other(format!(
+                            "{} is not a valid bitrate",
+                            value
+                        )));
+                    }
+                    options.max_bitrate = max_bitrate.unwrap();
+                    idx += 1;
+                }
+                "--codec" => {
+                    let value: &String = get_value(args, idx)?;
+                    let codec: Option<VideoCodec> = VideoCodec::parse(value);
+                    if codec.is_none() {
+                        return Err(io::Error::other(format!(
+                            "{} is not a supported codec",
+                            value
+                        )));
+                    }
+                    options.codec = codec.unwrap();
+                    idx += 1;
+                }
+                "--crf" => {
+                    options.crf = get_number::<u8>(args, idx)?;
+                    idx += 1;
+                }
+                "-n" | "--dry-run" => options.dry_run = true,
+                "--force" => options.force = true,
+                "--rules" => {
+                    options.rules_file = get_value(args, idx)?.to_string();
+                    idx += 1;
+                }
+                "-t" | "--trash" => options.trash = true,
+                "-g" | "--strip-gps" => options.strip_gps = true,
+                arg if arg.starts_with('-') => {
+                    return Err(io::Error::other(format!("unknown option {}", arg)));
+                }
+                arg => options.folders.push(arg.trim_matches('/').to_string()),
+            }
+            idx += 1;
+        }
+
+        options.root_dir = format!("{}/", options.root_dir.trim_end_matches('/'));
+        if options.dry_run && options.mode == Mode::Gallery {
+            return Err(io::Error::other("--dry-run is not supported by gallery"));
+        }
+        if options.crf > options.codec.max_crf() {
+            return Err(io::Error::other(format!(
+                "crf for {} must be between 0 and {}",
+                options.codec.as_str(),
+                options.codec.max_crf()
+            )));
+        }
+        if options.mode == Mode::Gallery && options.output_dir.is_none() {
+            return Err(io::Error::other("gallery needs --output <DIR>"));
+        }
+        if options.rules_file.is_empty() {
+            options.rules_file = format!("{}{}", options.root_dir, rules::RULES_FILE);
+        }
+        if let Some(output_dir) = &options.output_dir {
+            if options.trash {
+                return Err(io::Error::other(
+                    "--trash cannot be used with --output, originals are kept",
+                ));
+            }
+            // relative, .. and symlinked spellings all have to resolve before comparing
+            let output_dir: PathBuf = resolve_path(output_dir)?;
+            let root_dir: PathBuf = resolve_path(&options.root_dir)?;
+            if output_dir.starts_with(&root_dir) {
+                return Err(io::Error::other(
+                    "output directory must be outside of the root directory",
+                ));
+            }
+            options.output_dir = Some(format!(
+                "{}/",
+                output_dir.to_string_lossy().trim_end_matches('/')
+            ));
+        }
+        Ok(Some(options))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Option<Options>, io::Error> {
+        let args: Vec<String> = ["image_resizer", "sort", "-r", "/tmp/library"]
+            .iter()
+            .chain(args)
+            .map(|a| a.to_string())
+            .collect();
+        Options::parse(&String::from("/home/user"), &args)
+    }
+
+    #[test]
+    fn layout_is_trimmed() {
+        let options: Options = parse(&["-l", "%Y/%m/"]).unwrap().unwrap();
+        assert_eq!(options.layout, "%Y/%m");
+    }
+
+    #[test]
+    fn layout_stays_inside_root() {
+        assert!(parse(&["-l", "../%Y"]).is_err());
+        assert!(parse(&["-l", "%Y/../../%m"]).is_err());
+        assert!(parse(&["-l", "/srv/%Y"]).is_err());
+        assert!(parse(&["-l", "%Y/..%m"]).is_ok());
+    }
+}
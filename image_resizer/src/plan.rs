@@ -0,0 +1,317 @@
+use std::collections::{HashMap, HashSet};
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::file_type;
+use crate::file_type::FileType;
+use crate::options::{CollisionStrategy, Options, OutputFormat};
+use crate::rules::Rules;
+
+pub struct Job {
+    pub file_path: String,
+    pub file_type: FileType,
+    pub file_name: String,
+    pub needs_convert: bool,
+    pub max_size: Option<u32>,
+    pub quality: u8,
+    pub format: OutputFormat,
+}
+
+fn get_job(
+    file_path: &String,
+    options: &Options,
+    make_small_folders: &[String],
+    rules: Option<&Rules>,
+) -> Result<Job, String> {
+    let file_type: Option<FileType> = file_type::detect(file_path).map_err(|e| e.to_string())?;
+    if file_type.is_none() {
+        return Err(String::from("not an image"));
+    }
+    let file_type: FileType = file_type.unwrap();
+
+    let relative_file: &str = &file_path[options.root_dir.len()..];
+    let mut max_size: Option<u32> = if make_small_folders
+        .iter()
+        .any(|f| relative_file.starts_with(&format!("{}/", f)))
+    {
+        Some(options.max_size)
+    } else {
+        None
+    };
+    let mut quality: u8 = options.quality;
+    let mut format: OutputFormat = options.format;
+    if let Some(rules) = rules {
+        rules.apply(relative_file, &mut max_size, &mut quality, &mut format);
+    }
+
+    let path: PathBuf = match &options.output_dir {
+        Some(output_dir) => Path::new(output_dir).join(relative_file),
+        None => PathBuf::from(file_path),
+    };
+    let extension: &str = path.extension().and_then(|e| e.to_str()).unwrap_or("");
+    let needs_convert: bool = file_type != format.file_type() || !format.has_extension(extension);
+    // a RAW file cannot be made again from the JPEG, so it is never simply removed
+    if needs_convert && file_type == FileType::Raw && options.output_dir.is_none() && !options.trash
+    {
+        return Err(String::from(
+            "RAW originals are only converted with --output or --trash",
+        ));
+    }
+    let file_name: String = if needs_convert {
+        path.with_extension(format.extension())
+            .to_string_lossy()
+            .to_string()
+    } else {
+        path.to_string_lossy().to_string()
+    };
+
+    Ok(Job {
+        file_path: file_path.to_string(),
+        file_type,
+        file_name,
+        needs_convert,
+        max_size,
+        quality,
+        format,
+    })
+}
+
+pub fn plan_files(
+    files: &[String],
+    options: &Options,
+    make_small_folders: &[String],
+    rules: Option<&Rules>,
+) -> (Vec<Job>, Vec<(String, String)>) {
+    let mut jobs: Vec<Job> = vec![];
+    let mut skipped: Vec<(String, String)> = vec![];
+    for file_path in files {
+        match get_job(file_path, options, make_small_folders, rules) {
+            Ok(job) => jobs.push(job),
+            Err(reason) => skipped.push((file_path.to_string(), reason)),
+        }
+    }
+    (jobs, skipped)
+}
+
+pub fn get_suffixed_name(file_name: &str, taken: &HashSet<String>) -> String {
+    let path: &Path = Path::new(file_name);
+    let stem: String = path
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let extension: String = path
+        .extension()
+        .map(|e| format!(".{}", e.to_string_lossy()))
+        .unwrap_or_default();
+
+    let mut count: usize = 1;
+    loop {
+        let suffixed: String = path
+            .with_file_name(format!("{}-{}{}", stem, count, extension))
+            .to_string_lossy()
+            .to_string();
+        if !taken.contains(&suffixed) {
+            return suffixed;
+        }
+        count += 1;
+    }
+}
+
+pub fn resolve_collisions(
+    mut jobs: Vec<Job>,
+    skipped: &mut Vec<(String, String)>,
+    options: &Options,
+) -> Result<Vec<Job>, io::Error> {
+    jobs.sort_by(|a, b| a.file_path.cmp(&b.file_path));
+
+    // files that are not processed still hold their name when converting in place
+    let mut taken: HashSet<String> = HashSet::new();
+    if options.output_dir.is_none() {
+        taken.extend(skipped.iter().map(|s| s.0.to_string()));
+    }
+    // images that keep their own name always win it
+    let mut owners: HashMap<String, String> = HashMap::new();
+    for job in jobs.iter().filter(|j| !j.needs_convert) {
+        taken.insert(job.file_name.to_string());
+        owners.insert(job.file_name.to_string(), job.file_path.to_string());
+    }
+
+    let mut resolved: Vec<Job> = vec![];
+    let mut collisions: Vec<String> = vec![];
+    for mut job in jobs {
+        if !job.needs_convert {
+            resolved.push(job);
+            continue;
+        }
+        if !taken.contains(&job.file_name) {
+            taken.insert(job.file_name.to_string());
+            owners.insert(job.file_name.to_string(), job.file_path.to_string());
+            resolved.push(job);
+            continue;
+        }
+
+        let collision: String = match owners.get(&job.file_name) {
+            Some(owner) if owner != &job.file_name => {
+                format!("{} is also saved as {}", owner, job.file_name)
+            }
+            _ => format!("{} already exists", job.file_name),
+        };
+        match options.on_collision {
+            CollisionStrategy::Suffix => {
+                job.file_name = get_suffixed_name(&job.file_name, &taken);
+                taken.insert(job.file_name.to_string());
+                resolved.push(job);
+            }
+            CollisionStrategy::Skip => skipped.push((job.file_path, collision)),
+            CollisionStrategy::Fail => {
+                collisions.push(format!("{} ({})", job.file_path, collision));
+            }
+        }
+    }
+
+    if !collisions.is_empty() {
+        return Err(io::Error::other(format!(
+            "output names collide, nothing was changed:\n    {}",
+            collisions.join("\n    ")
+        )));
+    }
+    Ok(resolved)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs;
+    use std::process;
+
+    const JPEG: &[u8] = &[0xFF, 0xD8, 0xFF, 0xE0];
+    const PNG: &[u8] = &[0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A];
+    const BMP: &[u8] = b"BM";
+
+    // resolved jobs and skipped files
+    type Planned = (Result<Vec<Job>, io::Error>, Vec<(String, String)>);
+
+    fn get_test_dir(name: &str) -> String {
+        let dir: PathBuf =
+            env::temp_dir().join(format!("image_resizer_{}_{}", name, process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir.to_string_lossy().to_string()
+    }
+
+    fn get_options(root_dir: &str, on_collision: &str) -> Options {
+        let args: Vec<String> = ["image_resizer", "-r", root_dir, "-c", on_collision]
+            .iter()
+            .map(|a| a.to_string())
+            .collect();
+        Options::parse(&String::new(), &args).unwrap().unwrap()
+    }
+
+    fn plan(root_dir: &String, on_collision: &str, files: &[(&str, &[u8])]) -> Planned {
+        let mut file_paths: Vec<String> = vec![];
+        for (name, header) in files {
+            let file_path: String = format!("{}/{}", root_dir, name);
+            fs::write(&file_path, header).unwrap();
+            file_paths.push(file_path);
+        }
+        let options: Options = get_options(root_dir, on_collision);
+        let (jobs, mut skipped): (Vec<Job>, Vec<(String, String)>) =
+            plan_files(&file_paths, &options, &[], None);
+        let resolved: Result<Vec<Job>, io::Error> =
+            resolve_collisions(jobs, &mut skipped, &options);
+        fs::remove_dir_all(root_dir).unwrap();
+        (resolved, skipped)
+    }
+
+    fn get_names(jobs: &[Job], root_dir: &str) -> Vec<String> {
+        jobs.iter()
+            .map(|j| j.file_name[root_dir.len() + 1..].to_string())
+            .collect()
+    }
+
+    #[test]
+    fn jpeg_extensions_keep_their_names() {
+        let root_dir: String = get_test_dir("jpeg_names");
+        let (jobs, skipped) = plan(
+            &root_dir,
+            "suffix",
+            &[("a.jpg", JPEG), ("b.JPG", JPEG), ("c.jpeg", JPEG)],
+        );
+        let jobs: Vec<Job> = jobs.unwrap();
+        assert!(skipped.is_empty());
+        assert!(jobs.iter().all(|j| !j.needs_convert));
+        assert_eq!(get_names(&jobs, &root_dir), ["a.jpg", "b.JPG", "c.jpeg"]);
+    }
+
+    #[test]
+    fn different_extensions_do_not_collide() {
+        let root_dir: String = get_test_dir("different");
+        let (jobs, _) = plan(
+            &root_dir,
+            "fail",
+            &[("IMG_1.jpg", JPEG), ("IMG_1.png", PNG)],
+        );
+        assert_eq!(
+            get_names(&jobs.unwrap(), &root_dir),
+            ["IMG_1.jpg", "IMG_1.jpeg"]
+        );
+    }
+
+    #[test]
+    fn suffix_keeps_the_existing_name() {
+        let root_dir: String = get_test_dir("suffix");
+        let (jobs, skipped) = plan(
+            &root_dir,
+            "suffix",
+            &[("IMG_1.jpeg", JPEG), ("IMG_1.png", PNG), ("IMG_1.bmp", BMP)],
+        );
+        assert!(skipped.is_empty());
+        assert_eq!(
+            get_names(&jobs.unwrap(), &root_dir),
+            ["IMG_1-1.jpeg", "IMG_1.jpeg", "IMG_1-2.jpeg"]
+        );
+    }
+
+    #[test]
+    fn skipped_files_hold_their_names() {
+        let root_dir: String = get_test_dir("held");
+        let (jobs, skipped) = plan(
+            &root_dir,
+            "suffix",
+            &[("IMG_2.jpeg", b"notes"), ("IMG_2.png", PNG)],
+        );
+        assert_eq!(skipped.len(), 1);
+        assert_eq!(get_names(&jobs.unwrap(), &root_dir), ["IMG_2-1.jpeg"]);
+    }
+
+    #[test]
+    fn skip_leaves_the_colliding_file() {
+        let root_dir: String = get_test_dir("skip");
+        let (jobs, skipped) = plan(
+            &root_dir,
+            "skip",
+            &[("IMG_1.jpeg", JPEG), ("IMG_1.png", PNG)],
+        );
+        assert_eq!(get_names(&jobs.unwrap(), &root_dir), ["IMG_1.jpeg"]);
+        assert_eq!(
+            skipped,
+            [(
+                format!("{}/IMG_1.png", root_dir),
+                format!("{}/IMG_1.jpeg already exists", root_dir)
+            )]
+        );
+    }
+
+    #[test]
+    fn fail_changes_nothing() {
+        let root_dir: String = get_test_dir("fail");
+        let (jobs, _) = plan(&root_dir, "fail", &[("IMG_1.bmp", BMP), ("IMG_1.png", PNG)]);
+        let err: String = jobs.err().unwrap().to_string();
+        assert!(err.starts_with("output names collide, nothing was changed"));
+        assert!(err.contains(&format!(
+            "{}/IMG_1.png ({}/IMG_1.bmp is also saved as {}/IMG_1.jpeg)",
+            root_dir, root_dir, root_dir
+        )));
+    }
+}
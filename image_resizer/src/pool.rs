@@ -0,0 +1,57 @@
+use std::slice::Iter;
+use std::sync::mpsc;
+use std::sync::mpsc::{Receiver, Sender};
+use std::sync::Mutex;
+use std::thread;
+
+use crate::progress::Progress;
+
+// result of the work and the lines it printed
+type Output<R> = (R, Vec<String>);
+
+pub fn run<T, R, F>(items: &[T], threads: usize, work: F) -> Vec<R>
+where
+    T: Sync,
+    R: Send,
+    F: Fn(&T, &mut Vec<String>) -> R + Sync,
+{
+    let threads: usize = threads.clamp(1, items.len().max(1));
+    let queue: Mutex<Iter<T>> = Mutex::new(items.iter());
+    let progress: Mutex<Progress> = Mutex::new(Progress::new(items.len()));
+    let (sender, receiver): (Sender<Output<R>>, Receiver<Output<R>>) = mpsc::channel();
+
+    let mut results: Vec<R> = vec![];
+    thread::scope(|scope| {
+        for _ in 0..threads {
+            let sender: Sender<Output<R>> = sender.clone();
+            let queue: &Mutex<Iter<T>> = &queue;
+            let work: &F = &work;
+            scope.spawn(move || loop {
+                let item: Option<&T> = queue.lock().ok().and_then(|mut q| q.next());
+                if item.is_none() {
+                    break;
+                }
+
+                // lines are printed together so workers do not interleave
+                let mut log: Vec<String> = vec![];
+                let result: R = work(item.unwrap(), &mut log);
+                if sender.send((result, log)).is_err() {
+                    break;
+                }
+            });
+        }
+        drop(sender);
+
+        for (result, log) in receiver {
+            if let Ok(mut progress) = progress.lock() {
+                progress.print_lines(&log);
+                progress.add_file();
+            }
+            results.push(result);
+        }
+    });
+    if let Ok(mut progress) = progress.lock() {
+        progress.finish();
+    }
+    results
+}
@@ -0,0 +1,163 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+
+use crate::change;
+use crate::change::{Change, Encoded, Failure};
+use crate::helper;
+use crate::options::Options;
+use crate::plan::Job;
+use crate::pool;
+
+struct Preview {
+    file_path: String,
+    actions: Vec<String>,
+    dimensions: Option<(u32, u32)>,
+    new_dimensions: Option<(u32, u32)>,
+    bytes: u64,
+    new_bytes: u64,
+}
+
+fn get_dimensions_string(dimensions: Option<(u32, u32)>) -> String {
+    match dimensions {
+        Some((width, height)) => format!("{}x{}", width, height),
+        None => String::from("?"),
+    }
+}
+
+fn preview_file(job: &Job, options: &Options) -> Result<Preview, io::Error> {
+    let bytes: u64 = fs::metadata(&job.file_path)?.len();
+    let change: Change = change::get_change(job, options).map_err(Failure::into_error)?;
+    let mut preview: Preview = Preview {
+        file_path: job.file_path.to_string(),
+        actions: vec![],
+        dimensions: change.dimensions,
+        new_dimensions: change.dimensions,
+        bytes,
+        new_bytes: bytes,
+    };
+    let encoded: &Encoded = match &change.encoded {
+        Some(encoded) => encoded,
+        None => {
+            preview
+                .actions
+                .push(String::from(if options.output_dir.is_some() {
+                    "copy"
+                } else {
+                    "keep"
+                }));
+            return Ok(preview);
+        }
+    };
+
+    // the encoded image is only measured, never saved
+    if job.needs_convert {
+        preview
+            .actions
+            .push(format!("convert to {}", job.format.extension()));
+    }
+    if change.resized || encoded.scaled {
+        preview.actions.push(String::from("resize"));
+    }
+    if change.strip_gps {
+        preview.actions.push(String::from("strip gps"));
+    }
+    if options.target_size.is_some() {
+        preview.actions.push(format!("quality {}", encoded.quality));
+    }
+    preview.new_dimensions = Some((encoded.image.width(), encoded.image.height()));
+    preview.new_bytes = encoded.data.len() as u64;
+    Ok(preview)
+}
+
+fn get_saved_string(bytes: u64, new_bytes: u64) -> String {
+    if new_bytes > bytes {
+        format!("-{}", helper::get_size_string(new_bytes - bytes))
+    } else {
+        helper::get_size_string(bytes - new_bytes)
+    }
+}
+
+pub fn add_to_folder(
+    folders: &mut BTreeMap<String, (u64, u64)>,
+    relative_path: &str,
+    bytes: u64,
+    new_bytes: u64,
+) {
+    let folder: String = match relative_path.rfind('/') {
+        Some(idx) => relative_path[..idx].to_string(),
+        None => String::from("."),
+    };
+    let totals: &mut (u64, u64) = folders.entry(folder).or_insert((0, 0));
+    totals.0 += bytes;
+    totals.1 += new_bytes;
+}
+
+pub fn print_folders(folders: &BTreeMap<String, (u64, u64)>) {
+    if folders.is_empty() {
+        return;
+    }
+    println!();
+    println!("Folder\tBefore\tAfter\tSaved");
+    let mut total: (u64, u64) = (0, 0);
+    for (folder, (bytes, new_bytes)) in folders {
+        println!(
+            "{}\t{}\t{}\t{}",
+            folder,
+            helper::get_size_string(*bytes),
+            helper::get_size_string(*new_bytes),
+            get_saved_string(*bytes, *new_bytes)
+        );
+        total.0 += bytes;
+        total.1 += new_bytes;
+    }
+    println!(
+        "Total\t{}\t{}\t{}",
+        helper::get_size_string(total.0),
+        helper::get_size_string(total.1),
+        get_saved_string(total.0, total.1)
+    );
+}
+
+pub fn run(options: &Options, jobs: &[Job]) {
+    let mut previews: Vec<Result<Preview, (String, String)>> =
+        pool::run(jobs, options.threads, |job, _| {
+            preview_file(job, options).map_err(|e| (job.file_path.to_string(), e.to_string()))
+        });
+    previews.sort_by(|a, b| {
+        let path = |p: &Result<Preview, (String, String)>| match p {
+            Ok(preview) => preview.file_path.to_string(),
+            Err((file_path, _)) => file_path.to_string(),
+        };
+        path(a).cmp(&path(b))
+    });
+
+    // plain tab separated lines so a dry run can be saved and diffed
+    let mut folders: BTreeMap<String, (u64, u64)> = BTreeMap::new();
+    for preview in &previews {
+        let preview: &Preview = match preview {
+            Ok(preview) => preview,
+            Err((file_path, err)) => {
+                println!("{}\tfailed: {}", &file_path[options.root_dir.len()..], err);
+                continue;
+            }
+        };
+        let relative_path: &str = &preview.file_path[options.root_dir.len()..];
+        println!(
+            "{}\t{}\t{} -> {}\t{} -> {}",
+            relative_path,
+            preview.actions.join(", "),
+            get_dimensions_string(preview.dimensions),
+            get_dimensions_string(preview.new_dimensions),
+            helper::get_size_string(preview.bytes),
+            helper::get_size_string(preview.new_bytes)
+        );
+        add_to_folder(
+            &mut folders,
+            relative_path,
+            preview.bytes,
+            preview.new_bytes,
+        );
+    }
+    print_folders(&folders);
+}
@@ -0,0 +1,110 @@
+use rust_cli::ansi::Color;
+use rust_cli::ansi::Font;
+
+use std::io;
+use std::io::{IsTerminal, Write};
+use std::time::{Duration, Instant};
+
+pub struct Progress {
+    total_files: usize,
+    files: usize,
+    start: Instant,
+    last_draw: Option<Instant>,
+    visible: bool,
+}
+
+fn get_time_string(seconds: u64) -> String {
+    if seconds >= 3600 {
+        format!("{}h{:02}m", seconds / 3600, (seconds % 3600) / 60)
+    } else if seconds >= 60 {
+        format!("{}m{:02}s", seconds / 60, seconds % 60)
+    } else {
+        format!("{}s", seconds)
+    }
+}
+
+impl Progress {
+    pub fn new(total_files: usize) -> Progress {
+        Progress {
+            total_files,
+            files: 0,
+            start: Instant::now(),
+            last_draw: None,
+            // output that is piped into a file stays free of the bar
+            visible: io::stdout().is_terminal(),
+        }
+    }
+
+    pub fn add_file(&mut self) {
+        self.files += 1;
+        self.draw(false);
+    }
+
+    pub fn print_lines(&mut self, lines: &[String]) {
+        if lines.is_empty() {
+            return;
+        }
+        if self.visible {
+            rust_cli::ansi::erase::line();
+            rust_cli::ansi::cursor::line_start();
+        }
+        for line in lines {
+            println!("{}", line);
+        }
+        self.draw(true);
+    }
+
+    pub fn finish(&mut self) {
+        if self.visible {
+            self.draw(true);
+            println!();
+        }
+    }
+
+    fn get_eta(&self) -> String {
+        if self.files == 0 {
+            return String::from("--");
+        }
+        let elapsed: f64 = self.start.elapsed().as_secs_f64();
+        let remaining: f64 = elapsed / self.files as f64 * (self.total_files - self.files) as f64;
+        get_time_string(remaining.round() as u64)
+    }
+
+    fn draw(&mut self, force: bool) {
+        if !self.visible {
+            return;
+        }
+        if !force
+            && self
+                .last_draw
+                .is_some_and(|t| t.elapsed() < Duration::from_millis(100))
+        {
+            return;
+        }
+        self.last_draw = Some(Instant::now());
+
+        let percent: f64 = if self.total_files == 0 {
+            1.0
+        } else {
+            (self.files as f64 / self.total_files as f64).min(1.0)
+        };
+
+        let percent_bar_len: usize = 30;
+        let percent_bar: usize = (percent * percent_bar_len as f64) as usize;
+
+        rust_cli::ansi::erase::line();
+        rust_cli::ansi::cursor::line_start();
+        print!(
+            "    [{}{}] {: >3}% {}/{} images ETA {}{}{}",
+            "#".repeat(percent_bar),
+            ".".repeat(percent_bar_len - percent_bar),
+            (percent * 100.0) as usize,
+            self.files,
+            self.total_files,
+            Color::Cyan.as_str(),
+            self.get_eta(),
+            Font::Reset.as_str()
+        );
+        let _ = io::stdout().flush();
+    }
+}
@@ -0,0 +1,269 @@
+use std::fs;
+use std::io;
+
+use crate::options::OutputFormat;
+
+pub const RULES_FILE: &str = ".image_resizer_rules";
+
+struct Rule {
+    glob: Vec<String>,
+    max_size: Option<u32>,
+    quality: Option<u8>,
+    format: Option<OutputFormat>,
+}
+
+impl Rule {
+    fn new(glob: &str) -> Rule {
+        Rule {
+            glob: glob
+                .trim_matches('/')
+                .split('/')
+                .filter(|c| !c.is_empty())
+                .map(|c| c.to_string())
+                .collect(),
+            max_size: None,
+            quality: None,
+            format: None,
+        }
+    }
+
+    fn get_specificity(&self) -> (usize, usize) {
+        let components: usize = self.glob.iter().filter(|c| *c != "**").count();
+        let literals: usize = self
+            .glob
+            .iter()
+            .map(|c| c.chars().filter(|ch| *ch != '*' && *ch != '?').count())
+            .sum();
+        (components, literals)
+    }
+}
+
+pub struct Rules {
+    rules: Vec<Rule>,
+}
+
+fn matches_component(pattern: &[char], text: &[char]) -> bool {
+    match pattern.first() {
+        None => text.is_empty(),
+        Some('*') => (0..=text.len()).any(|i| matches_component(&pattern[1..], &text[i..])),
+        Some('?') => !text.is_empty() && matches_component(&pattern[1..], &text[1..]),
+        Some(ch) => text.first() == Some(ch) && matches_component(&pattern[1..], &text[1..]),
+    }
+}
+
+// a rule covers a folder and everything below it, but only on whole folder names
+fn matches_prefix(glob: &[String], folders: &[&str]) -> bool {
+    if glob.is_empty() {
+        return true;
+    }
+    if glob[0] == "**" {
+        return (0..=folders.len()).any(|i| matches_prefix(&glob[1..], &folders[i..]));
+    }
+    if folders.is_empty() {
+        return false;
+    }
+    let pattern: Vec<char> = glob[0].chars().collect();
+    let folder: Vec<char> = folders[0].chars().collect();
+    matches_component(&pattern, &folder) && matches_prefix(&glob[1..], &folders[1..])
+}
+
+fn get_error(path: &String, line_number: usize, message: String) -> io::Error {
+    io::Error::other(format!("{} line {}: {}", path, line_number, message))
+}
+
+impl Rules {
+    pub fn read(path: &String) -> Result<Option<Rules>, io::Error> {
+        let content: io::Result<String> = fs::read_to_string(path);
+        if let Err(err) = &content {
+            if err.kind() == io::ErrorKind::NotFound {
+                return Ok(None);
+            }
+        }
+
+        let mut rules: Vec<Rule> = vec![];
+        for (idx, line) in content?.lines().enumerate() {
+            let line: &str = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if line.starts_with('[') && line.ends_with(']') {
+                rules.push(Rule::new(&line[1..line.len() - 1]));
+                continue;
+            }
+
+            let key_value: Option<(&str, &str)> = line.split_once('=');
+            if key_value.is_none() {
+                return Err(get_error(
+                    path,
+                    idx + 1,
+                    format!("{} is not key=value", line),
+                ));
+            }
+            let (key, value): (&str, &str) = key_value.unwrap();
+            let (key, value): (&str, &str) = (key.trim(), value.trim());
+            let rule: Option<&mut Rule> = rules.last_mut();
+            if rule.is_none() {
+                return Err(get_error(
+                    path,
+                    idx + 1,
+                    format!("{} is not under a [folder]", key),
+                ));
+            }
+            let rule: &mut Rule = rule.unwrap();
+            match key {
+                "max_size" => {
+                    rule.max_size = value.parse::<u32>().ok().filter(|s| *s > 0);
+                    if rule.max_size.is_none() {
+                        return Err(get_error(path, idx + 1, format!("{} is not a size", value)));
+                    }
+                }
+                "quality" => {
+                    rule.quality = value.parse::<u8>().ok().filter(|q| *q > 0 && *q <= 100);
+                    if rule.quality.is_none() {
+                        return Err(get_error(
+                            path,
+                            idx + 1,
+                            format!("{} is not a quality", value),
+                        ));
+                    }
+                }
+                "format" => {
+                    rule.format = OutputFormat::parse(value);
+                    if rule.format.is_none() {
+                        return Err(get_error(
+                            path,
+                            idx + 1,
+                            format!("{} is not a format", value),
+                        ));
+                    }
+                }
+                key => return Err(get_error(path, idx + 1, format!("unknown key {}", key))),
+            }
+        }
+
+        // more specific rules are applied last so they win
+        rules.sort_by_key(|r| r.get_specificity());
+        Ok(Some(Rules { rules }))
+    }
+
+    pub fn apply(
+        &self,
+        relative_file: &str,
+        max_size: &mut Option<u32>,
+        quality: &mut u8,
+        format: &mut OutputFormat,
+    ) {
+        let mut folders: Vec<&str> = relative_file.split('/').collect();
+        folders.pop();
+        for rule in self
+            .rules
+            .iter()
+            .filter(|r| matches_prefix(&r.glob, &folders))
+        {
+            if rule.max_size.is_some() {
+                *max_size = rule.max_size;
+            }
+            if let Some(rule_quality) = rule.quality {
+                *quality = rule_quality;
+            }
+            if let Some(rule_format) = rule.format {
+                *format = rule_format;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::path::PathBuf;
+    use std::process;
+
+    fn get_folders(path: &str) -> Vec<&str> {
+        path.split('/').collect()
+    }
+
+    fn read_rules(name: &str, content: &str) -> Result<Option<Rules>, io::Error> {
+        let path: PathBuf =
+            env::temp_dir().join(format!("image_resizer_{}_{}", name, process::id()));
+        fs::write(&path, content).unwrap();
+        let rules: Result<Option<Rules>, io::Error> =
+            Rules::read(&path.to_string_lossy().to_string());
+        fs::remove_file(&path).unwrap();
+        rules
+    }
+
+    // max size, quality and format for a file after the rules are applied
+    fn apply(rules: &Rules, relative_file: &str) -> (Option<u32>, u8, OutputFormat) {
+        let mut max_size: Option<u32> = None;
+        let mut quality: u8 = 90;
+        let mut format: OutputFormat = OutputFormat::Jpeg;
+        rules.apply(relative_file, &mut max_size, &mut quality, &mut format);
+        (max_size, quality, format)
+    }
+
+    #[test]
+    fn prefix_matches_whole_folder_names() {
+        let glob: Vec<String> = Rule::new("/Trips/").glob;
+        assert!(matches_prefix(&glob, &get_folders("Trips")));
+        assert!(matches_prefix(&glob, &get_folders("Trips/Rome")));
+        assert!(!matches_prefix(&glob, &get_folders("Trips2020")));
+        assert!(!matches_prefix(&glob, &get_folders("Old/Trips")));
+        assert!(!matches_prefix(&glob, &[]));
+    }
+
+    #[test]
+    fn prefix_matches_wildcards() {
+        let glob: Vec<String> = Rule::new("Trips/20??*").glob;
+        assert!(matches_prefix(&glob, &get_folders("Trips/2020 Rome/day1")));
+        assert!(!matches_prefix(&glob, &get_folders("Trips/19")));
+
+        let glob: Vec<String> = Rule::new("**/Scans").glob;
+        assert!(matches_prefix(&glob, &get_folders("Scans")));
+        assert!(matches_prefix(&glob, &get_folders("Family/1990/Scans/box")));
+        assert!(!matches_prefix(&glob, &get_folders("Family/Scans2")));
+    }
+
+    #[test]
+    fn specific_rules_win() {
+        let rules: Rules = read_rules(
+            "specific",
+            "[Trips/Rome]\nquality = 70\n\n# everything else\n[**]\nmax_size = 800\nquality = 80\n\n[Trips]\nformat = png\n",
+        )
+        .unwrap()
+        .unwrap();
+
+        let (max_size, quality, format) = apply(&rules, "Trips/Rome/a.jpg");
+        assert_eq!((max_size, quality), (Some(800), 70));
+        assert!(format == OutputFormat::Png);
+
+        let (max_size, quality, format) = apply(&rules, "Trips2020/a.jpg");
+        assert_eq!((max_size, quality), (Some(800), 80));
+        assert!(format == OutputFormat::Jpeg);
+
+        let (max_size, quality, _) = apply(&rules, "a.jpg");
+        assert_eq!((max_size, quality), (Some(800), 80));
+    }
+
+    #[test]
+    fn missing_file_has_no_rules() {
+        let path: String = format!("/nonexistent/{}", RULES_FILE);
+        assert!(Rules::read(&path).unwrap().is_none());
+    }
+
+    #[test]
+    fn bad_lines_are_reported() {
+        let err: String = read_rules("bad_key", "[Trips]\nsize = 10\n")
+            .err()
+            .unwrap()
+            .to_string();
+        assert!(err.ends_with("line 2: unknown key size"));
+
+        let err: String = read_rules("no_folder", "quality = 10\n")
+            .err()
+            .unwrap()
+            .to_string();
+        assert!(err.ends_with("line 1: quality is not under a [folder]"));
+    }
+}
@@ -0,0 +1,172 @@
+use rust_cli::ansi::Color;
+use rust_cli::ansi::Font;
+
+use chrono::{DateTime, Local, NaiveDateTime};
+use std::collections::HashSet;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::time::SystemTime;
+
+use crate::file_type;
+use crate::metadata::Metadata;
+use crate::options::{CollisionStrategy, Options};
+use crate::plan;
+use crate::state;
+use crate::state::State;
+
+struct Move {
+    from: String,
+    to: String,
+    from_exif: bool,
+}
+
+fn get_date(file_path: &String) -> Result<(NaiveDateTime, bool), io::Error> {
+    if let Some(date) = Metadata::read_file(file_path).get_date_taken() {
+        return Ok((date, true));
+    }
+    let modified: SystemTime = fs::metadata(file_path)?.modified()?;
+    Ok((DateTime::<Local>::from(modified).naive_local(), false))
+}
+
+fn move_file(from: &String, to: &String) -> Result<(), io::Error> {
+    if Path::new(to).exists() {
+        return Err(io::Error::other(format!("{} already exists", to)));
+    }
+    if let Some(parent) = Path::new(to).parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::rename(from, to)
+}
+
+pub fn run(options: &Options, all_files: &[String], files: &[String]) -> Result<(), io::Error> {
+    let mut files: Vec<&String> = files.iter().collect();
+    files.sort();
+
+    let mut taken: HashSet<String> = all_files.iter().cloned().collect();
+    let mut moves: Vec<Move> = vec![];
+    let mut skipped: Vec<(String, String)> = vec![];
+    let mut collisions: Vec<String> = vec![];
+    for file_path in files {
+        if !file_type::detect(file_path).is_ok_and(|t| t.is_some()) {
+            continue;
+        }
+        let (date, from_exif): (NaiveDateTime, bool) = match get_date(file_path) {
+            Ok(date) => date,
+            Err(err) => {
+                skipped.push((file_path.to_string(), err.to_string()));
+                continue;
+            }
+        };
+
+        let file_name: String = Path::new(file_path)
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let mut to: String = format!(
+            "{}{}/{}",
+            options.root_dir,
+            date.format(&options.layout),
+            file_name
+        );
+        if &to == file_path {
+            continue;
+        }
+        if taken.contains(&to) {
+            match options.on_collision {
+                CollisionStrategy::Suffix => to = plan::get_suffixed_name(&to, &taken),
+                CollisionStrategy::Skip => {
+                    skipped.push((file_path.to_string(), format!("{} already exists", to)));
+                    continue;
+                }
+                CollisionStrategy::Fail => {
+                    collisions.push(format!("{} ({} already exists)", file_path, to));
+                    continue;
+                }
+            }
+        }
+        taken.insert(to.to_string());
+        moves.push(Move {
+            from: file_path.to_string(),
+            to,
+            from_exif,
+        });
+    }
+    if !collisions.is_empty() {
+        return Err(io::Error::other(format!(
+            "sorted names collide, nothing was moved:\n    {}",
+            collisions.join("\n    ")
+        )));
+    }
+
+    let mut state: State = State::read(options);
+    let mut moved: usize = 0;
+    let mut renamed: usize = 0;
+    for m in &moves {
+        // one line per file so a dry run can be reviewed and diffed
+        println!(
+            "{} -> {}{}",
+            &m.from[options.root_dir.len()..],
+            &m.to[options.root_dir.len()..],
+            if m.from_exif { "" } else { " (file time)" }
+        );
+        if options.dry_run {
+            continue;
+        }
+        match move_file(&m.from, &m.to) {
+            Ok(_) => {
+                if state.rename(&m.from, &m.to) {
+                    renamed += 1;
+                }
+                moved += 1;
+            }
+            Err(err) => println!(
+                "    {}Failed{} to move: {}",
+                Color::Red.as_str(),
+                Font::Reset.as_str(),
+                err
+            ),
+        }
+    }
+
+    // a library that was never resized has no state to keep up to date
+    if renamed > 0 {
+        if let Err(err) = state.write() {
+            println!(
+                "{}Failed{} to save {}: {}",
+                Color::Red.as_str(),
+                Font::Reset.as_str(),
+                state::STATE_FILE,
+                err
+            );
+        }
+    }
+
+    if !skipped.is_empty() {
+        println!(
+            "Skipped {}{}{} files:",
+            Color::Yellow.as_str(),
+            skipped.len(),
+            Font::Reset.as_str()
+        );
+        for (file_path, reason) in &skipped {
+            println!("    {} ({})", file_path, reason);
+        }
+    }
+    if options.dry_run {
+        println!(
+            "Would move {}{}{} images, nothing was changed",
+            Color::Cyan.as_str(),
+            moves.len(),
+            Font::Reset.as_str()
+        );
+    } else {
+        println!(
+            "Moved {}{}{} images",
+            Color::Cyan.as_str(),
+            moved,
+            Font::Reset.as_str()
+        );
+    }
+    Ok(())
+}
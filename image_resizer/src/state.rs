@@ -0,0 +1,159 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::time::UNIX_EPOCH;
+
+use crate::options::Options;
+use crate::plan::Job;
+
+pub const STATE_FILE: &str = ".image_resizer_state";
+
+#[derive(Clone, PartialEq)]
+pub struct FileState {
+    pub mtime: u64,
+    pub size: u64,
+    pub settings: String,
+    pub result: String,
+}
+
+impl FileState {
+    pub fn read(file: &String, settings: String, result: String) -> Result<FileState, io::Error> {
+        let metadata: fs::Metadata = fs::metadata(file)?;
+        let mtime: u64 = metadata
+            .modified()?
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        Ok(FileState {
+            mtime,
+            size: metadata.len(),
+            settings,
+            result,
+        })
+    }
+}
+
+pub struct State {
+    path: String,
+    root_dir: String,
+    files: HashMap<String, FileState>,
+}
+
+pub fn get_settings(options: &Options, job: &Job) -> String {
+    // a file is only unchanged if it was processed the same way last time
+    format!(
+        "{} q{} {} {} {} {}",
+        job.format.extension(),
+        job.quality,
+        job.max_size
+            .map(|s| s.to_string())
+            .unwrap_or(String::from("-")),
+        options
+            .target_size
+            .map(|s| format!("{}b", s))
+            .unwrap_or(String::from("-")),
+        if options.strip_gps { "strip-gps" } else { "-" },
+        options.output_dir.as_deref().unwrap_or("-")
+    )
+}
+
+impl State {
+    pub fn read(options: &Options) -> State {
+        // a mirror run keeps its state next to the copies, the library stays untouched
+        let state_dir: &String = options.output_dir.as_ref().unwrap_or(&options.root_dir);
+        let mut state: State = State {
+            path: format!("{}{}", state_dir, STATE_FILE),
+            root_dir: options.root_dir.to_string(),
+            files: HashMap::new(),
+        };
+        let content: io::Result<String> = fs::read_to_string(&state.path);
+        if content.is_err() {
+            return state;
+        }
+
+        for line in content.unwrap().lines() {
+            let parts: Vec<&str> = line.splitn(5, '\t').collect();
+            if parts.len() != 5 {
+                continue;
+            }
+            state.files.insert(
+                parts[4].to_string(),
+                FileState {
+                    mtime: parts[0].parse::<u64>().unwrap_or(0),
+                    size: parts[1].parse::<u64>().unwrap_or(0),
+                    settings: parts[2].to_string(),
+                    result: parts[3].to_string(),
+                },
+            );
+        }
+        state
+    }
+
+    pub fn write(&self) -> Result<(), io::Error> {
+        // files that were converted away or removed are forgotten
+        let mut paths: Vec<&String> = self
+            .files
+            .keys()
+            .filter(|p| Path::new(&format!("{}{}", self.root_dir, p)).exists())
+            .collect();
+        paths.sort();
+
+        let mut content: String = String::new();
+        for path in paths {
+            let state: &FileState = &self.files[path];
+            content.push_str(&format!(
+                "{}\t{}\t{}\t{}\t{}\n",
+                state.mtime, state.size, state.settings, state.result, path
+            ));
+        }
+        if let Some(parent) = Path::new(&self.path).parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&self.path, content)
+    }
+
+    pub fn is_state_file(&self, file: &String) -> bool {
+        // the library can still hold one from an earlier in place run
+        file == &self.path || file == &format!("{}{}", self.root_dir, STATE_FILE)
+    }
+
+    pub fn is_unchanged(&self, job: &Job, settings: &String) -> bool {
+        let previous: Option<&FileState> = self.files.get(&job.file_path[self.root_dir.len()..]);
+        if previous.is_none() {
+            return false;
+        }
+        let previous: &FileState = previous.unwrap();
+        let current: io::Result<FileState> = FileState::read(
+            &job.file_path,
+            settings.to_string(),
+            previous.result.to_string(),
+        );
+        if current.is_err() {
+            return false;
+        }
+        // mirrored copies have to still be there as well
+        current.unwrap() == *previous && Path::new(&job.file_name).exists()
+    }
+
+    // a moved file keeps its content and time, so it keeps its state too
+    pub fn rename(&mut self, from: &str, to: &str) -> bool {
+        if !from.starts_with(&self.root_dir) || !to.starts_with(&self.root_dir) {
+            return false;
+        }
+        let file_state: Option<FileState> = self.files.remove(&from[self.root_dir.len()..]);
+        if file_state.is_none() {
+            return false;
+        }
+        self.files
+            .insert(to[self.root_dir.len()..].to_string(), file_state.unwrap());
+        true
+    }
+
+    pub fn set(&mut self, file: &str, file_state: FileState) {
+        if file.starts_with(&self.root_dir) {
+            self.files
+                .insert(file[self.root_dir.len()..].to_string(), file_state);
+        }
+    }
+}
@@ -0,0 +1,248 @@
+use chrono::prelude::Local;
+use std::env;
+use std::fs;
+use std::fs::{DirBuilder, File, Metadata};
+use std::io;
+use std::io::Write;
+use std::os::unix::fs::{DirBuilderExt, MetadataExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+
+use crate::helper;
+
+struct TrashDir {
+    files_dir: PathBuf,
+    info_dir: PathBuf,
+    // a trash on another volume keeps paths relative to the top of that volume
+    top_dir: Option<PathBuf>,
+}
+
+impl TrashDir {
+    fn new(trash_dir: &Path, top_dir: Option<PathBuf>) -> Result<TrashDir, io::Error> {
+        let trash: TrashDir = TrashDir {
+            files_dir: trash_dir.join("files"),
+            info_dir: trash_dir.join("info"),
+            top_dir,
+        };
+        fs::create_dir_all(&trash.files_dir)?;
+        fs::create_dir_all(&trash.info_dir)?;
+        Ok(trash)
+    }
+
+    fn create_info(&self, file: &Path) -> Result<(String, File), io::Error> {
+        let name: String = file
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let stem: String = file
+            .file_stem()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let extension: String = file
+            .extension()
+            .map(|e| format!(".{}", e.to_string_lossy()))
+            .unwrap_or_default();
+
+        let mut count: usize = 1;
+        loop {
+            let trash_name: String = if count == 1 {
+                name.to_string()
+            } else {
+                format!("{}.{}{}", stem, count, extension)
+            };
+            let info_file: io::Result<File> = File::options()
+                .write(true)
+                .create_new(true)
+                .open(self.info_dir.join(format!("{}.trashinfo", trash_name)));
+            match info_file {
+                Ok(info_file) if !self.files_dir.join(&trash_name).exists() => {
+                    return Ok((trash_name, info_file));
+                }
+                Ok(_) => {
+                    fs::remove_file(self.info_dir.join(format!("{}.trashinfo", trash_name)))?;
+                }
+                Err(err) if err.kind() == io::ErrorKind::AlreadyExists => (),
+                Err(err) => return Err(err),
+            }
+            count += 1;
+        }
+    }
+
+    fn get_info(&self, file: &Path, deletion_date: &str) -> String {
+        let path: &Path = match &self.top_dir {
+            Some(top_dir) => file.strip_prefix(top_dir).unwrap_or(file),
+            None => file,
+        };
+        format!(
+            "[Trash Info]\nPath={}\nDeletionDate={}\n",
+            helper::encode_path(&path.to_string_lossy()),
+            deletion_date
+        )
+    }
+
+    fn put(&self, file: &Path) -> Result<(), io::Error> {
+        let (trash_name, mut info_file): (String, File) = self.create_info(file)?;
+        info_file.write_all(
+            self.get_info(file, &Local::now().format("%Y-%m-%dT%H:%M:%S").to_string())
+                .as_bytes(),
+        )?;
+
+        let trash_file: PathBuf = self.files_dir.join(&trash_name);
+        if fs::rename(file, &trash_file).is_err() {
+            if let Err(err) = fs::copy(file, &trash_file) {
+                fs::remove_file(self.info_dir.join(format!("{}.trashinfo", trash_name)))?;
+                return Err(err);
+            }
+            fs::remove_file(file)?;
+        }
+        Ok(())
+    }
+}
+
+pub struct Trash {
+    home: TrashDir,
+    device: u64,
+    uid: u32,
+}
+
+// the highest folder that is still on the same volume as the file
+fn get_top_dir(file: &Path, device: u64) -> PathBuf {
+    let mut top_dir: &Path = file;
+    while let Some(parent) = top_dir.parent() {
+        if !fs::metadata(parent).is_ok_and(|m| m.dev() == device) {
+            break;
+        }
+        top_dir = parent;
+    }
+    top_dir.to_path_buf()
+}
+
+impl Trash {
+    pub fn new(home_dir: &String) -> Result<Trash, io::Error> {
+        let data_dir: String = env::var("XDG_DATA_HOME")
+            .ok()
+            .filter(|d| !d.is_empty())
+            .unwrap_or(format!("{}/.local/share", home_dir));
+        let trash_dir: PathBuf = Path::new(&data_dir).join("Trash");
+
+        let home: TrashDir = TrashDir::new(&trash_dir, None)?;
+        // the home trash belongs to the user running this
+        let metadata: Metadata = fs::metadata(&trash_dir)?;
+        Ok(Trash {
+            home,
+            device: metadata.dev(),
+            uid: metadata.uid(),
+        })
+    }
+
+    fn get_volume_trash(&self, top_dir: &Path) -> Option<TrashDir> {
+        // a shared $topdir/.Trash only counts when it is a real sticky folder
+        let shared_dir: PathBuf = top_dir.join(".Trash");
+        if fs::symlink_metadata(&shared_dir)
+            .is_ok_and(|m| m.is_dir() && m.permissions().mode() & 0o1000 != 0)
+        {
+            let trash: io::Result<TrashDir> = TrashDir::new(
+                &shared_dir.join(self.uid.to_string()),
+                Some(top_dir.to_path_buf()),
+            );
+            if let Ok(trash) = trash {
+                return Some(trash);
+            }
+        }
+
+        let user_dir: PathBuf = top_dir.join(format!(".Trash-{}", self.uid));
+        if let Err(err) = DirBuilder::new().mode(0o700).create(&user_dir) {
+            if err.kind() != io::ErrorKind::AlreadyExists {
+                return None;
+            }
+        }
+        let metadata: Metadata = fs::symlink_metadata(&user_dir).ok()?;
+        if !metadata.is_dir() || metadata.uid() != self.uid {
+            return None;
+        }
+        TrashDir::new(&user_dir, Some(top_dir.to_path_buf())).ok()
+    }
+
+    pub fn put(&self, file: &String) -> Result<(), io::Error> {
+        let file: PathBuf = fs::canonicalize(file)?;
+        // files on another volume go to the trash of that volume, so they are not copied
+        let device: u64 = fs::metadata(&file)?.dev();
+        if device != self.device {
+            if let Some(trash) = self.get_volume_trash(&get_top_dir(&file, device)) {
+                return trash.put(&file);
+            }
+        }
+        self.home.put(&file)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process;
+
+    fn get_test_dir(name: &str) -> PathBuf {
+        let dir: PathBuf =
+            env::temp_dir().join(format!("image_resizer_{}_{}", name, process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        fs::canonicalize(dir).unwrap()
+    }
+
+    #[test]
+    fn home_info_has_absolute_path() {
+        let dir: PathBuf = get_test_dir("home_trash");
+        let trash: TrashDir = TrashDir::new(&dir.join("Trash"), None).unwrap();
+        assert_eq!(
+            trash.get_info(
+                Path::new("/home/user/My Photos/a.jpg"),
+                "2024-05-01T10:00:00"
+            ),
+            "[Trash Info]\nPath=/home/user/My%20Photos/a.jpg\nDeletionDate=2024-05-01T10:00:00\n"
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn volume_info_has_relative_path() {
+        let dir: PathBuf = get_test_dir("volume_trash");
+        let trash: TrashDir = TrashDir::new(&dir.join(".Trash-1000"), Some(dir.clone())).unwrap();
+        assert_eq!(
+            trash.get_info(&dir.join("Trip/a b.jpg"), "2024-05-01T10:00:00"),
+            "[Trash Info]\nPath=Trip/a%20b.jpg\nDeletionDate=2024-05-01T10:00:00\n"
+        );
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn put_moves_file_and_writes_info() {
+        let dir: PathBuf = get_test_dir("put_trash");
+        let trash: TrashDir = TrashDir::new(&dir.join(".Trash-1000"), Some(dir.clone())).unwrap();
+        fs::create_dir_all(dir.join("Trip")).unwrap();
+        for _ in 0..2 {
+            fs::write(dir.join("Trip/a.jpg"), "photo").unwrap();
+            trash.put(&dir.join("Trip/a.jpg")).unwrap();
+        }
+
+        assert!(!dir.join("Trip/a.jpg").exists());
+        assert!(trash.files_dir.join("a.jpg").is_file());
+        assert!(trash.files_dir.join("a.2.jpg").is_file());
+        let info: String = fs::read_to_string(trash.info_dir.join("a.2.jpg.trashinfo")).unwrap();
+        assert!(info.starts_with("[Trash Info]\nPath=Trip/a.jpg\nDeletionDate="));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn top_dir_stays_on_the_volume() {
+        let dir: PathBuf = get_test_dir("top_dir");
+        let file: PathBuf = dir.join("a.jpg");
+        fs::write(&file, "photo").unwrap();
+        let device: u64 = fs::metadata(&file).unwrap().dev();
+        let top_dir: PathBuf = get_top_dir(&file, device);
+        assert!(file.starts_with(&top_dir));
+        assert_eq!(fs::metadata(&top_dir).unwrap().dev(), device);
+        if let Some(parent) = top_dir.parent() {
+            assert_ne!(fs::metadata(parent).unwrap().dev(), device);
+        }
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
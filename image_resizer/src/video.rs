@@ -0,0 +1,388 @@
+use rust_cli::ansi::Color;
+use rust_cli::ansi::Font;
+
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::path::Path;
+use std::process::{Command, Output, Stdio};
+use std::time::SystemTime;
+
+use crate::file_type;
+use crate::helper;
+use crate::options::{Options, VideoCodec};
+use crate::pool;
+use crate::preview;
+use crate::trash::Trash;
+
+enum Encoding {
+    Saved(u64),
+    Larger(u64),
+}
+
+struct Clip {
+    file_path: String,
+    file_name: String,
+    muxer: &'static str,
+    width: u32,
+    height: u32,
+    bitrate: u64,
+    duration: f64,
+    bytes: u64,
+}
+
+impl Clip {
+    fn needs_resize(&self, options: &Options) -> bool {
+        self.width.max(self.height) > options.video_size
+    }
+
+    fn needs_encode(&self, options: &Options) -> bool {
+        self.needs_resize(options) || self.bitrate > options.max_bitrate
+    }
+
+    // ffmpeg encodes to a quality, not a bitrate, so this is a guess from the bitrate limit
+    fn get_estimated_bytes(&self, options: &Options) -> u64 {
+        if !self.needs_encode(options) || self.duration <= 0.0 {
+            return self.bytes;
+        }
+        let (new_width, new_height): (u32, u32) = self.get_new_dimensions(options);
+        let scale: f64 =
+            (new_width as f64 * new_height as f64) / (self.width as f64 * self.height as f64);
+        let bitrate: f64 = self.bitrate.min(options.max_bitrate) as f64 * scale;
+        // an encode that comes out larger is thrown away
+        ((bitrate * self.duration / 8.0) as u64).min(self.bytes)
+    }
+
+    fn get_new_dimensions(&self, options: &Options) -> (u32, u32) {
+        if !self.needs_resize(options) {
+            return (self.width, self.height);
+        }
+        let size: u32 = get_even(options.video_size);
+        let scale = |side: u32| -> u32 {
+            let scaled: f64 = side as f64 * size as f64 / self.width.max(self.height) as f64;
+            ((scaled / 2.0).round() as u32 * 2).max(2)
+        };
+        if self.width >= self.height {
+            (size, scale(self.height))
+        } else {
+            (scale(self.width), size)
+        }
+    }
+}
+
+// most encoders only take even dimensions
+fn get_even(size: u32) -> u32 {
+    size - size % 2
+}
+
+fn run_ffmpeg(command: &mut Command, program: &str) -> Result<String, io::Error> {
+    let output: Output = command.stdin(Stdio::null()).output().map_err(|err| {
+        if err.kind() == io::ErrorKind::NotFound {
+            io::Error::other(format!("videos need {} to be installed", program))
+        } else {
+            err
+        }
+    })?;
+    if !output.status.success() {
+        let stderr: String = String::from_utf8_lossy(&output.stderr).to_string();
+        return Err(io::Error::other(format!(
+            "{} failed: {}",
+            program,
+            stderr.lines().last().unwrap_or("unknown error")
+        )));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+fn probe(options: &Options, file_path: &String, muxer: &'static str) -> Result<Clip, io::Error> {
+    let output: String = run_ffmpeg(
+        Command::new("ffprobe")
+            .args(["-v", "error", "-select_streams", "v:0"])
+            .args([
+                "-show_entries",
+                "stream=width,height:stream_tags=rotate:stream_side_data=rotation:format=bit_rate,duration",
+            ])
+            .args(["-of", "default=noprint_wrappers=1"])
+            .arg(file_path),
+        "ffprobe",
+    )?;
+    let values: HashMap<&str, &str> = output
+        .lines()
+        .filter_map(|l| l.split_once('='))
+        .map(|(k, v)| (k.trim(), v.trim()))
+        .collect();
+    let get = |key: &str| values.get(key).and_then(|v| v.parse::<f64>().ok());
+
+    let (mut width, mut height): (f64, f64) = match (get("width"), get("height")) {
+        (Some(width), Some(height)) => (width, height),
+        _ => return Err(io::Error::other("no video stream found")),
+    };
+    // phones store portrait clips as landscape frames with a rotation next to them
+    let rotation: f64 = get("rotation").or(get("TAG:rotate")).unwrap_or(0.0);
+    if (rotation.abs() as u32) % 180 == 90 {
+        (width, height) = (height, width);
+    }
+    let bytes: u64 = fs::metadata(file_path)?.len();
+    // some containers only know their duration, not their bitrate
+    let bitrate: f64 = match (get("bit_rate"), get("duration")) {
+        (Some(bitrate), _) => bitrate,
+        (None, Some(duration)) if duration > 0.0 => bytes as f64 * 8.0 / duration,
+        _ => 0.0,
+    };
+    let duration: f64 = match get("duration") {
+        Some(duration) => duration,
+        None if bitrate > 0.0 => bytes as f64 * 8.0 / bitrate,
+        None => 0.0,
+    };
+    let file_name: String = match &options.output_dir {
+        Some(output_dir) => format!("{}{}", output_dir, &file_path[options.root_dir.len()..]),
+        None => file_path.to_string(),
+    };
+    Ok(Clip {
+        file_path: file_path.to_string(),
+        file_name,
+        muxer,
+        width: width as u32,
+        height: height as u32,
+        bitrate: bitrate as u64,
+        duration,
+        bytes,
+    })
+}
+
+fn get_action(clip: &Clip, options: &Options) -> String {
+    if !clip.needs_encode(options) {
+        return String::from(if options.output_dir.is_some() {
+            "copy"
+        } else {
+            "keep"
+        });
+    }
+    let mut action: String = format!("encode {} crf {}", options.codec.as_str(), options.crf);
+    if clip.needs_resize(options) {
+        action.push_str(", resize");
+    }
+    action
+}
+
+fn encode_clip(
+    clip: &Clip,
+    options: &Options,
+    trash: Option<&Trash>,
+) -> Result<Encoding, io::Error> {
+    if let Some(parent) = Path::new(&clip.file_name).parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let temp_file: String = format!("{}.tmp", clip.file_name);
+    let mut command: Command = Command::new("ffmpeg");
+    command
+        .args(["-v", "error", "-y", "-i"])
+        .arg(&clip.file_path)
+        .args(["-map", "0:v:0", "-map", "0:a?", "-map_metadata", "0"])
+        .args(["-c:a", "copy", "-c:v", options.codec.encoder()])
+        .args(["-crf", &options.crf.to_string()]);
+    if clip.needs_resize(options) {
+        // the limit is on the long side, which may be either one once ffmpeg applies the rotation
+        let size: u32 = get_even(options.video_size);
+        command.arg("-vf").arg(format!(
+            "scale='if(gte(iw,ih),min(iw,{}),-2)':'if(gte(iw,ih),-2,min(ih,{}))'",
+            size, size
+        ));
+    }
+    if clip.muxer != "matroska" {
+        // keeps QuickTime tags like com.apple.quicktime.creationdate next to creation_time
+        command.args(["-movflags", "use_metadata_tags"]);
+        if options.codec == VideoCodec::H265 {
+            command.args(["-tag:v", "hvc1"]);
+        }
+    }
+    command.args(["-f", clip.muxer]).arg(&temp_file);
+
+    let modified: SystemTime = fs::metadata(&clip.file_path)?.modified()?;
+    let written: Result<(), io::Error> = run_ffmpeg(&mut command, "ffmpeg").and_then(|_| {
+        let temp: File = File::options().write(true).open(&temp_file)?;
+        // apps that ignore the metadata still sort the clip by its file time
+        temp.set_modified(modified)?;
+        temp.sync_all()
+    });
+    if let Err(err) = written {
+        let _ = fs::remove_file(&temp_file);
+        return Err(err);
+    }
+
+    let new_bytes: u64 = fs::metadata(&temp_file)?.len();
+    if new_bytes >= clip.bytes {
+        fs::remove_file(&temp_file)?;
+        if options.output_dir.is_some() {
+            fs::copy(&clip.file_path, &clip.file_name)?;
+        }
+        return Ok(Encoding::Larger(new_bytes));
+    }
+    if let Some(trash) = trash {
+        if Path::new(&clip.file_name).exists() {
+            trash.put(&clip.file_name)?;
+        }
+    }
+    fs::rename(&temp_file, &clip.file_name)?;
+    Ok(Encoding::Saved(clip.bytes - new_bytes))
+}
+
+pub fn run(options: &Options, files: &[String], trash: Option<&Trash>) -> Result<(), io::Error> {
+    let mut videos: Vec<(&String, &'static str)> = vec![];
+    for file_path in files {
+        if let Ok(Some(muxer)) = file_type::detect_video(file_path) {
+            videos.push((file_path, muxer));
+        }
+    }
+
+    let probed: Vec<Result<Clip, (String, String)>> =
+        pool::run(&videos, options.threads, |(file_path, muxer), _| {
+            probe(options, file_path, muxer).map_err(|e| (file_path.to_string(), e.to_string()))
+        });
+    let mut clips: Vec<Clip> = vec![];
+    let mut skipped: Vec<(String, String)> = vec![];
+    for clip in probed {
+        match clip {
+            Ok(clip) => clips.push(clip),
+            Err(skip) => skipped.push(skip),
+        }
+    }
+    // a missing ffprobe would only repeat the same reason for every video
+    if !videos.is_empty()
+        && clips.is_empty()
+        && skipped.iter().all(|(_, r)| r.contains("installed"))
+    {
+        return Err(io::Error::other(skipped[0].1.to_string()));
+    }
+    clips.sort_by(|a, b| a.file_path.cmp(&b.file_path));
+
+    let to_encode: Vec<&Clip> = clips.iter().filter(|c| c.needs_encode(options)).collect();
+    if options.dry_run {
+        // plain tab separated lines, like the image dry run
+        let mut folders: BTreeMap<String, (u64, u64)> = BTreeMap::new();
+        for clip in &clips {
+            let relative_path: &str = &clip.file_path[options.root_dir.len()..];
+            let (new_width, new_height): (u32, u32) = clip.get_new_dimensions(options);
+            let new_bytes: u64 = clip.get_estimated_bytes(options);
+            println!(
+                "{}\t{}\t{}x{} -> {}x{}\t{}\t{} -> ~{}",
+                relative_path,
+                get_action(clip, options),
+                clip.width,
+                clip.height,
+                new_width,
+                new_height,
+                helper::get_bitrate_string(clip.bitrate),
+                helper::get_size_string(clip.bytes),
+                helper::get_size_string(new_bytes)
+            );
+            preview::add_to_folder(&mut folders, relative_path, clip.bytes, new_bytes);
+        }
+        preview::print_folders(&folders);
+    } else {
+        if options.output_dir.is_some() {
+            for clip in clips.iter().filter(|c| !c.needs_encode(options)) {
+                if let Some(parent) = Path::new(&clip.file_name).parent() {
+                    fs::create_dir_all(parent)?;
+                }
+                fs::copy(&clip.file_path, &clip.file_name)?;
+            }
+        }
+        // ffmpeg already uses every core, so clips are encoded one at a time
+        let results: Vec<Option<(&Clip, Encoding)>> = pool::run(&to_encode, 1, |clip, log| {
+            let (new_width, new_height): (u32, u32) = clip.get_new_dimensions(options);
+            log.push(format!(
+                "Encoding {}{}{} ({}x{}, {})...",
+                Color::Cyan.as_str(),
+                clip.file_path,
+                Font::Reset.as_str(),
+                clip.width,
+                clip.height,
+                helper::get_bitrate_string(clip.bitrate)
+            ));
+            match encode_clip(clip, options, trash) {
+                Ok(Encoding::Saved(saved)) => {
+                    log.push(format!(
+                        "    {}Saved{} {} at {}x{}",
+                        Color::Green.as_str(),
+                        Font::Reset.as_str(),
+                        helper::get_size_string(saved),
+                        new_width,
+                        new_height
+                    ));
+                    Some((*clip, Encoding::Saved(saved)))
+                }
+                Ok(Encoding::Larger(new_bytes)) => {
+                    log.push(format!(
+                        "    {}Kept{} the original, encoding gave {}",
+                        Color::Yellow.as_str(),
+                        Font::Reset.as_str(),
+                        helper::get_size_string(new_bytes)
+                    ));
+                    Some((*clip, Encoding::Larger(new_bytes)))
+                }
+                Err(err) => {
+                    log.push(format!(
+                        "    {}Failed{} ({})",
+                        Color::Red.as_str(),
+                        Font::Reset.as_str(),
+                        err
+                    ));
+                    None
+                }
+            }
+        });
+        let mut saved: Vec<u64> = vec![];
+        for (clip, encoding) in results.into_iter().flatten() {
+            match encoding {
+                Encoding::Saved(bytes) => saved.push(bytes),
+                Encoding::Larger(new_bytes) => skipped.push((
+                    clip.file_path.to_string(),
+                    format!(
+                        "encoding gave {}, the original was kept",
+                        helper::get_size_string(new_bytes)
+                    ),
+                )),
+            }
+        }
+        println!(
+            "Encoded {}{}{} of {} videos, saved {}",
+            Color::Cyan.as_str(),
+            saved.len(),
+            Font::Reset.as_str(),
+            to_encode.len(),
+            helper::get_size_string(saved.iter().sum())
+        );
+    }
+
+    if !skipped.is_empty() {
+        skipped.sort();
+        println!(
+            "Skipped {}{}{} videos:",
+            Color::Yellow.as_str(),
+            skipped.len(),
+            Font::Reset.as_str()
+        );
+        for (file_path, reason) in &skipped {
+            println!("    {} ({})", file_path, reason);
+        }
+    }
+    if options.dry_run {
+        println!(
+            "Would encode {}{}{} of {} videos ({}), saving about {}, nothing was changed",
+            Color::Cyan.as_str(),
+            to_encode.len(),
+            Font::Reset.as_str(),
+            clips.len(),
+            helper::get_size_string(to_encode.iter().map(|c| c.bytes).sum()),
+            helper::get_size_string(
+                to_encode
+                    .iter()
+                    .map(|c| c.bytes - c.get_estimated_bytes(options))
+                    .sum()
+            )
+        );
+    }
+    Ok(())
+}